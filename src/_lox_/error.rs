@@ -0,0 +1,47 @@
+use crate::parser::error::{EvalError, ParserError};
+use crate::tokenizer::token_type::TokenType;
+use thiserror::Error;
+
+/// A problem found while scanning source text, located by line and column
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub line: usize,
+    pub col: usize,
+    pub message: String,
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at line {}, column {}", self.message, self.line, self.col)
+    }
+}
+
+/// Why a call to [Lox::run](crate::Lox::run) failed
+#[derive(Error, Debug, PartialEq)]
+pub enum LoxError {
+    /// The source could not be tokenized, nothing was parsed or run
+    #[error("{} scan error(s)", _0.len())]
+    Scan(Vec<Diagnostic>),
+    /// The tokens could not be parsed, nothing was run
+    #[error("{} parse error(s)", _0.len())]
+    Parse(Vec<ParserError>),
+    /// A REPL line ran out of tokens, holds what was expected next (`MISSING_OPERAND` for an expression).
+    /// The line is fine so far and needs more input rather than being a syntax error
+    #[error("Incomplete input, expected {}", _0.to_string())]
+    Incomplete(TokenType),
+    /// The first error raised while running the program. The interpreter reports
+    /// runtime errors as they happen and carries on with the next statement
+    #[error("{0}")]
+    Runtime(EvalError),
+}
+
+impl LoxError {
+    /// Number of errors this stands for, a runtime error only holds the first one
+    pub fn error_count(&self) -> usize {
+        match self {
+            LoxError::Scan(diagnostics) => diagnostics.len(),
+            LoxError::Parse(errors) => errors.len(),
+            LoxError::Incomplete(_) | LoxError::Runtime(_) => 1,
+        }
+    }
+}
@@ -3,12 +3,30 @@ use crate::{
     parser::{error::RuntimeError, value::Value},
     tokenizer::token::Token,
 };
-use std::{cell::RefCell, collections::HashMap, rc::Rc};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    rc::Rc,
+};
+
+/// What [Memory::get] found for a variable
+#[derive(Debug, Clone, PartialEq)]
+pub enum ResolvedVar {
+    /// The variable holds this value, which may well be nil
+    Defined(Value),
+    /// Declared with `var x;` and not assigned to yet
+    Uninitialized,
+}
 
 /// An environment for executing [Statements](crate::parser::statement::Declaration)s
 #[derive(Debug, Clone, PartialEq)]
 pub struct Environment {
     pub values: HashMap<String, Value>,
+    /// Names declared without an initializer (`var x;`) that haven't been assigned yet.
+    /// Reading one of these is an error, whereas an explicit `var x = nil;` reads as nil
+    uninitialized: HashSet<String>,
+    /// Names declared with `const`, assigning to one of these is an error
+    constants: HashSet<String>,
     /// Enclosing scope, for global scope it's none
     /// The parent environment may be shared by multiple scopes and require interior mutablity for ops
     /// therefore it makes sense to have a RefCell which allows us to obtain a mutable ref to inner Environment
@@ -17,96 +35,98 @@ pub struct Environment {
     enclosing: Option<Rc<RefCell<Environment>>>,
     is_global: bool,
     inside_loop: bool,
+    /// Length of the scope chain ending here, 1 for the global scope
+    depth: usize,
 }
 impl Default for Environment {
     fn default() -> Self {
         Self {
             values: Default::default(),
+            uninitialized: Default::default(),
+            constants: Default::default(),
             inside_loop: false,
             enclosing: None,
             is_global: true,
+            depth: 1,
         }
     }
 }
 impl Environment {
+    /// Create a global environment with `pairs` already defined
+    pub fn preloaded(pairs: impl IntoIterator<Item = (String, Value)>) -> Self {
+        Self { values: pairs.into_iter().collect(), ..Default::default() }
+    }
     /// Create a new environment with an enclosing environment
     pub fn enclosed_by(enclosing: Rc<RefCell<Environment>>) -> Self {
+        let depth = enclosing.borrow().depth + 1;
         let enclosing = Some(Rc::clone(&enclosing));
         Self {
             // If surrounded by an environment, cannot be global
             is_global: false,
             enclosing,
+            depth,
             ..Default::default()
         }
     }
     /// Create a new environment for loop
     pub fn loop_enclosed_by(enclosing: Rc<RefCell<Environment>>) -> Self {
+        let depth = enclosing.borrow().depth + 1;
         let enclosing = Some(Rc::clone(&enclosing));
         Self {
             // If surrounded by an environment, cannot be global
             is_global: false,
             inside_loop: true,
             enclosing,
+            depth,
             ..Default::default()
         }
     }
     pub fn in_loop(&self) -> bool {
         self.inside_loop
     }
+    /// Number of scopes from this one out to the global scope, both included
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+    /// Forget every binding in this scope, enclosing scopes are untouched
+    pub fn clear_scope(&mut self) {
+        self.values.clear();
+        self.uninitialized.clear();
+        self.constants.clear();
+    }
 }
 impl Memory for Rc<RefCell<Environment>> {
     fn define(&self, name: &str, value: Value) {
         // If previous was something, the user just used var x = _ syntax to reassign to x instead of
         // x = _ syntax
         let _previous: Option<Value> = self.borrow_mut().values.insert(name.to_owned(), value);
+        self.borrow_mut().uninitialized.remove(name);
+        self.borrow_mut().constants.remove(name);
     }
-    fn get(&self, token: &Token) -> Result<Option<Value>, RuntimeError> {
-        // crate::loc!(format!("{:?}", self.values));
-        let name = token.lexeme.clone();
-        match self.borrow().values.get(&name) {
-            Some(val) if *val == Value::Nil => Ok(None),
-            Some(val) => Ok(Some(val.to_owned())),
-            None => {
-                let current_env: Rc<RefCell<Environment>> = Rc::clone(&self);
-                // We either find a value in enclosing scopes or none
-                // no clue why this is caught as unused assignment
-                // It was an unused assignment becz we never read the RHS ( = None )
-                let scoped_val: Option<Value>;
-                '_check_scopes: loop {
-                    if let Some(ref encl_env) = current_env.borrow().enclosing {
-                        if let Ok(Some(val)) = encl_env.get(&token) {
-                            break scoped_val = Some(val);
-                        } else if let Ok(None) = encl_env.get(&token) {
-                            // Variable declared but has Nil initializer
-                            break scoped_val = None;
-                        } else {
-                            // IF you get a panic for BorrowMut, it's unequivocably this line at fault
-                            current_env.swap(encl_env);
-                            continue;
-                        }
-                    }
-                    // No enclosing environment, current_env is global env
-                    // Upto this we have not found the var declared
-                    else {
-                        assert!(
-                            current_env.borrow().is_global,
-                            "ICE: Current env expected to be global at this point"
-                        );
-                        let encl_borrow = current_env.borrow();
-                        match encl_borrow.values.get(&name) {
-                            Some(val) if *val == Value::Nil => return Ok(None),
-                            Some(val) => return Ok(Some(val.to_owned())),
-                            None => {
-                                return Err(RuntimeError::UncaughtReference(
-                                    token.clone(),
-                                    format!("variable '{name}' is not defined"),
-                                ))
-                            }
-                        }
-                    }
-                } // Loop ends at current_env = global scope
-                return Ok(scoped_val);
-            }
+    fn define_const(&self, name: &str, value: Value) {
+        self.define(name, value);
+        self.borrow_mut().constants.insert(name.to_owned());
+    }
+    fn declare(&self, name: &str) {
+        let mut env = self.borrow_mut();
+        env.values.insert(name.to_owned(), Value::Nil);
+        env.uninitialized.insert(name.to_owned());
+        env.constants.remove(name);
+    }
+    fn get(&self, token: &Token) -> Result<ResolvedVar, RuntimeError> {
+        let name = &token.lexeme;
+        let env = self.borrow();
+        match env.values.get(name) {
+            Some(_) if env.uninitialized.contains(name) => Ok(ResolvedVar::Uninitialized),
+            Some(val) => Ok(ResolvedVar::Defined(val.to_owned())),
+            // Not in this scope, the enclosing scopes are searched outwards up to the global scope
+            None => match &env.enclosing {
+                Some(enclosing) => enclosing.get(token),
+                None => Err(RuntimeError::UncaughtReference(
+                    token.clone(),
+                    format!("variable '{name}' is not defined"),
+                )),
+            },
         }
     }
     fn put(&self, name: &str, value: Value) -> Result<(), RuntimeError> {
@@ -122,8 +142,12 @@ impl Memory for Rc<RefCell<Environment>> {
             }
             break;
         }
+        if self.borrow().constants.contains(name) {
+            return Err(RuntimeError::AssignToConst(name.to_owned()));
+        }
         if self.borrow().values.contains_key(name) {
             self.borrow_mut().values.insert(name.to_owned(), value);
+            self.borrow_mut().uninitialized.remove(name);
         } else if nested_found {
             return Ok(());
         } else {
@@ -2,6 +2,7 @@ use crate::loc;
 use crate::parser::error::{RuntimeError, EvalError};
 use crate::parser::value::LoxFunction;
 use crate::parser::{
+    expressions::Expression,
     statement::Stmt,
     traits::evaluate::Evaluate,
     value::{Value, ValueResult},
@@ -13,8 +14,11 @@ use std::cell::RefCell;
 use std::rc::Rc;
 mod environment;
 mod native_fn;
+mod output;
 use native_fn::*;
-pub use environment::Environment;
+pub use environment::{Environment, ResolvedVar};
+pub use output::{InputSource, OutputSink, SharedBuffer};
+use crate::parser::value::{Falsy, NumberFormat};
 
 #[derive(Debug)]
 #[allow(dead_code)]
@@ -25,27 +29,151 @@ pub struct Interpreter {
     /// Tracks the current execution context
     env: Rc<RefCell<Environment>>,
     pub(crate) repl: bool,
-    // index for repl mode
+    /// Index of the first statement that hasn't been run yet, so that the REPL only runs new lines
     previous: usize,
+    /// Random number generator backing the `random` family of natives
+    pub(crate) rng: Prng,
+    /// Where program output is written
+    output: OutputSink,
+    /// Where runtime errors are reported
+    errors: OutputSink,
+    /// Where the `read_line` native reads from
+    input: InputSource,
+    /// Write only what the program prints, without the `>> ` before echoed values or the lines
+    /// announcing declarations. Set by [Lox::eval_capture](crate::Lox::eval_capture)
+    pub(crate) raw_output: bool,
+    /// Line of the innermost statement that failed, taken when the error is reported
+    error_line: Option<usize>,
+    /// Lox functions currently executing, outermost first, with the line each was called from
+    pub(crate) call_stack: Vec<(String, usize)>,
+    /// How numbers are displayed, set by the `set_number_format` native
+    pub(crate) number_format: NumberFormat,
+    /// Number of Lox function calls currently executing, 0 at the top level
+    pub(crate) call_depth: usize,
+    /// What the `exit` native calls, terminates the process unless replaced
+    pub(crate) exit_hook: ExitHook,
+    /// Most iterations a single `while` or `for` loop may run, unlimited when `None`
+    iteration_limit: Option<usize>,
+    /// Deepest scope chain a statement may run in, see [Environment::depth]
+    scope_limit: usize,
+    /// When set, every statement is written to the output before it runs and every expression with its value
+    trace: bool,
+    /// Number of `try` blocks currently executing. While it's above 0, errors inside blocks
+    /// propagate to the nearest `catch` instead of being reported and skipped
+    try_depth: usize,
+    /// When set, `+` only joins strings with strings and chars
+    strict_concat: bool,
+    /// Number of runtime errors reported so far
+    errors_reported: usize,
+    /// Takes over reporting uncaught runtime errors when set, see [Interpreter::set_error_handler]
+    error_handler: Option<ErrorHandler>,
+    /// Values that count as false in conditions on top of `false` and `nil`, set by the `set_falsy` native
+    pub(crate) falsy: Falsy,
+}
+
+/// Scopes may nest this deep unless [Interpreter::set_scope_limit] says otherwise
+pub const DEFAULT_SCOPE_LIMIT: usize = 256;
+
+/// Receives every runtime error that isn't caught by a `try`, in place of the built-in report
+pub struct ErrorHandler(pub Box<dyn FnMut(&EvalError)>);
+
+impl std::fmt::Debug for ErrorHandler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "ErrorHandler")
+    }
 }
 
 impl Default for Interpreter {
     fn default() -> Self {
-        let global_env = Rc::new(RefCell::new(Environment::default()));
-        global_env.define("clock", Value::Function(Rc::new(Clock)));
-        Self { stmts: vec![], globals:Rc::clone(&global_env), env : global_env, repl: false, previous: 0 }
+        let global_env = setup_globals();
+        Self {
+            stmts: vec![],
+            globals: Rc::clone(&global_env),
+            env: global_env,
+            repl: false,
+            previous: 0,
+            rng: Prng::from_time(),
+            output: OutputSink::stdout(),
+            errors: OutputSink::stderr(),
+            input: InputSource::stdin(),
+            raw_output: false,
+            error_line: None,
+            call_stack: vec![],
+            number_format: NumberFormat::default(),
+            call_depth: 0,
+            exit_hook: ExitHook::default(),
+            iteration_limit: None,
+            scope_limit: DEFAULT_SCOPE_LIMIT,
+            trace: false,
+            try_depth: 0,
+            strict_concat: false,
+            errors_reported: 0,
+            error_handler: None,
+            falsy: Falsy::default(),
+        }
     }
 }
+/// A fresh global environment with every native defined, shared by all constructors
+fn setup_globals() -> Rc<RefCell<Environment>> {
+    let globals = Rc::new(RefCell::new(Environment::default()));
+    define_natives(&globals);
+    globals
+}
+/// Register every native function in `globals`
+fn define_natives(globals: &Rc<RefCell<Environment>>) {
+    globals.define("clock", Value::Function(Rc::new(Clock)));
+    globals.define("random", Value::Function(Rc::new(Random)));
+    globals.define("random_int", Value::Function(Rc::new(RandomInt)));
+    globals.define("seed", Value::Function(Rc::new(Seed)));
+    globals.define("set_number_format", Value::Function(Rc::new(SetNumberFormat)));
+    globals.define("set_falsy", Value::Function(Rc::new(SetFalsy)));
+    globals.define("char_at", Value::Function(Rc::new(CharAt)));
+    globals.define("len", Value::Function(Rc::new(Len)));
+    globals.define("contains", Value::Function(Rc::new(Contains)));
+    globals.define("exit", Value::Function(Rc::new(Exit)));
+    globals.define("sort", Value::Function(Rc::new(Sort)));
+    globals.define("div", Value::Function(Rc::new(Div)));
+    globals.define("error", Value::Function(Rc::new(Raise)));
+    globals.define("get_or", Value::Function(Rc::new(GetOr)));
+    globals.define("int", Value::Function(Rc::new(Int)));
+    globals.define("approx_eq", Value::Function(Rc::new(ApproxEq)));
+    globals.define("starts_with", Value::Function(Rc::new(StartsWith)));
+    globals.define("ends_with", Value::Function(Rc::new(EndsWith)));
+    globals.define("trim", Value::Function(Rc::new(Trim)));
+    globals.define("chars", Value::Function(Rc::new(Chars)));
+    globals.define("globals", Value::Function(Rc::new(Globals)));
+    globals.define("reduce", Value::Function(Rc::new(Reduce)));
+    globals.define("hex_encode", Value::Function(Rc::new(HexEncode)));
+    globals.define("hex_decode", Value::Function(Rc::new(HexDecode)));
+    globals.define("version", Value::Function(Rc::new(Version)));
+    globals.define("assert_eq", Value::Function(Rc::new(AssertEq)));
+    globals.define("assert_true", Value::Function(Rc::new(AssertTrue)));
+    globals.define("read_file", Value::Function(Rc::new(ReadFile)));
+    globals.define("write_file", Value::Function(Rc::new(WriteFile)));
+    globals.define("map", Value::Function(Rc::new(NewMap)));
+    globals.define("has_key", Value::Function(Rc::new(HasKey)));
+    globals.define("map_get", Value::Function(Rc::new(MapGet)));
+    globals.define("map_set", Value::Function(Rc::new(MapSet)));
+    globals.define("map_delete", Value::Function(Rc::new(MapDelete)));
+    globals.define("read_line", Value::Function(Rc::new(ReadLine)));
+    globals.define("abs", Value::Function(Rc::new(Abs)));
+    globals.define("sign", Value::Function(Rc::new(Sign)));
+    globals.define("clamp", Value::Function(Rc::new(Clamp)));
+}
 pub trait Memory {
     fn define(&self, name: &str, value: Value);
-    fn get(&self, name: &Token) -> Result<Option<Value>, RuntimeError>;
+    /// Declare `name` without initializing it, reading it before assignment is an error
+    fn declare(&self, name: &str);
+    /// Define `name` as a constant, `put` refuses to reassign it
+    fn define_const(&self, name: &str, value: Value);
+    /// Look `name` up in this scope and then the enclosing ones, an error if no scope declares it
+    fn get(&self, name: &Token) -> Result<ResolvedVar, RuntimeError>;
     fn put(&self, name: &str, value: Value) -> Result<(), RuntimeError>;
 }
 
 impl Interpreter {
     pub fn new(mut p: Parser) -> Self {
-        let global_env = Rc::new(RefCell::new(Environment::default()));
-        global_env.define("clock", Value::Function(Rc::new(Clock)));
+        let global_env = setup_globals();
         Self {
             stmts: p.parse(),
             globals : Rc::clone(&global_env),
@@ -53,26 +181,154 @@ impl Interpreter {
             ..Default::default()
         }
     }
-    /// Extend stmts with statements and also set Environment to `env`
-    /// Currently used for tests only
-    pub fn extend_with_env(&mut self, mut stmts: Vec<Stmt>, env: Rc<RefCell<Environment>>) {
+    /// Create an interpreter for already parsed `stmts`
+    pub fn with_stmts(stmts: Vec<Stmt>) -> Self {
+        Self { stmts, ..Default::default() }
+    }
+    /// Extend stmts with statements and also set Environment to `env` before interpreting them,
+    /// like [extend](Interpreter::extend) outside of repl mode. Currently used for tests only
+    pub fn extend_with_env(&mut self, mut stmts: Vec<Stmt>, env: Rc<RefCell<Environment>>) -> Result<(), EvalError> {
         self.env = env;
         self.previous = self.stmts.len();
         self.stmts.append(&mut stmts);
         loc!(format!("Interpreter modified -> {self:?}"));
-        self.interpret();
+        self.interpret()
+    }
+    /// Is a Lox function body currently being executed? A `return` is only legal when this is true,
+    /// otherwise it is an [EvalError::ReturnOutsideFunction]
+    pub fn in_function(&self) -> bool {
+        self.call_depth > 0
+    }
+    /// Redirect program output to `writer`
+    pub fn set_output(&mut self, writer: impl std::io::Write + 'static) {
+        self.output = OutputSink::new(writer);
+    }
+    /// Redirect program output to `sink`, handing back the sink that was in use
+    pub fn replace_output(&mut self, sink: OutputSink) -> OutputSink {
+        std::mem::replace(&mut self.output, sink)
+    }
+    /// Read program input from `reader` instead of stdin
+    pub fn set_input(&mut self, reader: impl std::io::BufRead + 'static) {
+        self.input = InputSource::new(reader);
+    }
+    /// Read a line of program input. The output is flushed first so that a prompt written before
+    /// the read shows up even when the output is fully buffered, as it is when piped
+    pub(crate) fn read_line(&mut self) -> std::io::Result<Option<String>> {
+        self.output.flush();
+        self.input.read_line()
+    }
+    /// Turn the execution trace on or off, it's off by default
+    pub fn set_trace(&mut self, trace: bool) {
+        self.trace = trace;
+    }
+    pub(crate) fn tracing(&self) -> bool {
+        self.trace
+    }
+    /// Write a line of the execution trace to the output sink
+    pub(crate) fn trace(&mut self, line: &str) {
+        self.output.write_line(&format!("[trace] {line}"));
+    }
+    /// Redirect runtime error reports to `writer`
+    pub fn set_error_output(&mut self, writer: impl std::io::Write + 'static) {
+        self.errors = OutputSink::new(writer);
+    }
+    /// Report a runtime error, prefixed by the line of the statement that raised it and followed
+    /// by the function calls it was raised in, innermost first. Errors inside a function body are
+    /// reported by the body's block, so the calls are still on the stack
+    fn report_error(&mut self, e: &EvalError) {
+        self.errors_reported += 1;
+        if let Some(ErrorHandler(handler)) = &mut self.error_handler {
+            self.error_line = None;
+            return handler(e);
+        }
+        let message = match self.error_line.take() {
+            Some(line) => format!("{} [line {line}] {e}", "Interpreter Error:".red()),
+            None => format!("{} {e}", "Interpreter Error:".red()),
+        };
+        self.errors.write_line(&message);
+        let trace: Vec<String> =
+            self.call_stack.iter().rev().map(|(name, line)| format!("  in {name}(), called at line {line}")).collect();
+        for line in trace {
+            self.errors.write_line(&line);
+        }
+    }
+    /// How many runtime errors have been reported, errors caught by a `try` don't count
+    pub fn errors_reported(&self) -> usize {
+        self.errors_reported
+    }
+    /// Hand uncaught runtime errors to `handler` instead of writing them, with their line and
+    /// traceback, to the error output. Lets an embedder show errors its own way
+    pub fn set_error_handler(&mut self, handler: impl FnMut(&EvalError) + 'static) {
+        self.error_handler = Some(ErrorHandler(Box::new(handler)));
+    }
+    /// Replace what the `exit` native does, by default it terminates the process
+    pub fn set_exit_hook(&mut self, hook: impl Fn(i32) + 'static) {
+        self.exit_hook = ExitHook(Box::new(hook));
+    }
+    /// Abort any `while` or `for` loop that runs more than `limit` iterations with
+    /// [EvalError::IterationLimitExceeded], handy in the REPL where a runaway loop hangs the session.
+    /// `None` removes the limit, which is the default
+    pub fn set_iteration_limit(&mut self, limit: Option<usize>) {
+        self.iteration_limit = limit;
+    }
+    /// Make `"a" + 1`, `"a" + true` and `"a" + nil` errors rather than concatenating the number, bool
+    /// or nil as text. Concatenation is lenient by default
+    pub fn set_strict_concat(&mut self, strict: bool) {
+        self.strict_concat = strict;
+    }
+    pub(crate) fn strict_concat(&self) -> bool {
+        self.strict_concat
+    }
+    /// Fail statements that would run in a scope chain longer than `limit` with [EvalError::ScopeTooDeep]
+    pub fn set_scope_limit(&mut self, limit: usize) {
+        self.scope_limit = limit;
+    }
+    /// Flush any buffered program output
+    pub(crate) fn flush_output(&mut self) {
+        self.output.flush();
+    }
+    /// Display `value` honouring the current number format
+    pub fn format_value(&self, value: &Value) -> String {
+        value.format_with(&self.number_format)
+    }
+    /// Write a line of program output to the output sink
+    pub(crate) fn emit(&mut self, line: &str) {
+        self.output.write_line(line);
+    }
+    /// Echo the value a statement produced
+    fn echo(&mut self, value: &Value) {
+        let line = if self.raw_output {
+            self.format_value(value)
+        } else {
+            format!(">> {}", self.format_value(value))
+        };
+        self.emit(&line);
+    }
+    /// Tell the user about a declaration, left out of raw output
+    fn announce(&mut self, line: &str) {
+        if !self.raw_output {
+            self.emit(line);
+        }
+    }
+    /// Drop every user defined global and forget previously run statements,
+    /// leaving only the native functions. Backs the REPL `:reset` command
+    pub fn reset_globals(&mut self) {
+        self.globals.borrow_mut().clear_scope();
+        define_natives(&self.globals);
+        self.env = Rc::clone(&self.globals);
+        self.stmts.clear();
+        self.previous = 0;
     }
     /// Extend a repl interpreter and interpret the added stmts
-    pub fn extend(&mut self, mut p: Parser) {
+    pub fn extend(&mut self, mut stmts: Vec<Stmt>) -> Result<(), EvalError> {
         assert!(
             self.repl,
             "ICE : Extend can only be called on repl mode, call interpret() instead"
         );
         self.previous = self.stmts.len();
-        self.stmts.append(&mut p.parse());
+        self.stmts.append(&mut stmts);
         loc!(format!("Interpreter modified -> {self:?}"));
-        self.interpret();
-        // if self.is_repl_mode ? then for stmt in self.stmts[self.previous..].iter() { .. }
+        self.interpret()
     }
     /// Execute a block of statements inside environment `sub_env`
     pub fn execute_block(
@@ -82,57 +338,84 @@ impl Interpreter {
         inside_loop: bool
     ) -> ValueResult {
         for stmt in statements.iter() {
-            // check if a statement is a loop, if yes, set `inside_loop`
-            let loop_stmt = if matches!(stmt, Stmt::While { .. }) {
-                true
-            } else { false };
-            match self.execute(&stmt, Rc::clone(&sub_env), loop_stmt || inside_loop) {
-                Ok(val) if matches!(val, Value::Break) => {
+            match self.execute(&stmt, Rc::clone(&sub_env), inside_loop) {
+                Ok(val) if matches!(val, Value::Break(_) | Value::Return(_)) => {
                     // Early return
-                    return Ok(Value::Break);
+                    return Ok(val);
                 }
                 Ok(val) => {
                     if val != Value::Nil {
-                        println!(">> {}", val);
+                        self.echo(&val);
                     }
                 }
+                Err(e) if self.try_depth > 0 => return Err(e),
                 Err(e) => {
                     loc!();
-                    eprintln!("{} {e}", "Interpreter Error:".red());
+                    self.report_error(&e);
                 }
             };
         }
         Ok(Value::Nil)
     }
+    /// Run `body` in `loop_env` for as long as `condition` holds in `rc_env`. Evaluates to the
+    /// [Value::Break] or [Value::Return] that ended the loop if there was one, otherwise to what the last
+    /// iteration produced
+    pub(crate) fn run_while(
+        &mut self,
+        condition: &Expression,
+        body: &Stmt,
+        rc_env: &Rc<RefCell<Environment>>,
+        loop_env: Rc<RefCell<Environment>>,
+    ) -> ValueResult {
+        let mut val = Value::Nil;
+        let mut iterations = 0usize;
+        while condition.eval(rc_env, self)?.is_truthy_in(self) {
+            iterations += 1;
+            if let Some(limit) = self.iteration_limit && iterations > limit {
+                return Err(EvalError::IterationLimitExceeded(limit));
+            }
+            val = self.execute(body, Rc::clone(&loop_env), true)?;
+            if matches!(val, Value::Break(_) | Value::Return(_)) {
+                break;
+            }
+        }
+        Ok(val)
+    }
     /// Execute a statement inside a new environment `rc_env`
     pub fn execute(&mut self, stmt: &Stmt, rc_env: Rc<RefCell<Environment>>, inside_loop: bool) -> ValueResult {
+        match self.execute_stmt(stmt, rc_env, inside_loop) {
+            // A `return` that went off inside an expression continues from here like any other
+            Err(err) if matches!(err.innermost(), EvalError::ReturnFromExpression(_)) => match err.into_innermost() {
+                EvalError::ReturnFromExpression(value) => Ok(Value::Return(value)),
+                _ => unreachable!(),
+            },
+            result => result,
+        }
+    }
+    fn execute_stmt(&mut self, stmt: &Stmt, rc_env: Rc<RefCell<Environment>>, inside_loop: bool) -> ValueResult {
         // Create a new environment surrounded by rc_env
         let inside_env = RefCell::new(if inside_loop {
             Environment::loop_enclosed_by(Rc::clone(&rc_env))
         } else {
             Environment::enclosed_by(Rc::clone(&rc_env))
         });
+        if inside_env.borrow().depth() > self.scope_limit {
+            return Err(EvalError::ScopeTooDeep(self.scope_limit));
+        }
         match stmt {
+            // Only the REPL shows what an expression statement evaluated to, at the top level and in blocks alike
             Stmt::ExprStmt(e) => {
-                    match **e {
-                        crate::parser::expressions::Expression::Assignment(_)
-                        | crate::parser::expressions::Expression::Variable(_) => {
-                            let _a = e.eval(&rc_env, self);
-                            if _a.is_ok() && !self.repl { 
-                                Ok(Value::Nil) }
-                            else { _a }
-                        },
-                        _ =>  e.eval(&rc_env, self)
-                    }                                        
+                    let value = e.eval(&rc_env, self)?;
+                    if self.repl { Ok(value) } else { Ok(Value::Nil) }
+            }
+            Stmt::Print(Some(x)) => x.eval(&Rc::clone(&rc_env), self),
+            Stmt::Print(None) => {
+                self.emit("");
+                Ok(Value::Nil)
             }
-            Stmt::Print(x) => x.eval(&Rc::clone(&rc_env), self),
             Stmt::ErrStmt { message } => {
                 loc!();
-                eprintln!(
-                    "{}{}{message}",
-                    "Interpreter Error: ".red(),
-                    "Bad statement ".yellow()
-                );
+                self.report_error(&EvalError::BadStatement(message.clone()));
                 Ok(Value::Nil)
             }
             Stmt::Empty => Ok(Value::Nil),
@@ -147,58 +430,195 @@ impl Interpreter {
                 else_,
             } => {
                 // println!(" Got a {_ifstmt}");
-                // Exec the condition in current env
-                let condition_value = condition.eval(&Rc::clone(&rc_env),self)?;
-                // create a new environment
+                // create a new environment, shared by every link of an `else if` chain
                 let if_else = Rc::new(inside_env);
-                let mut val = Value::Nil;
-                if condition_value.is_truthy() {
-                    val = self.execute(then_.as_ref(), if_else, inside_loop)?;
+                let (mut condition, mut then_, mut else_) = (condition, then_, else_);
+                // Line of the `else if` link being run, errors in it are reported there
+                let mut link_line = None;
+                let result = loop {
+                    // Exec the condition in current env
+                    match condition.eval(&Rc::clone(&rc_env), self) {
+                        Ok(value) if value.is_truthy_in(self) => {
+                            break self.execute(then_.as_ref(), if_else, inside_loop);
+                        }
+                        Ok(_) => {}
+                        Err(err) => break Err(err),
+                    }
+                    let Some(else_branch) = else_.as_deref() else { break Ok(Value::Nil) };
+                    match else_branch.inner() {
+                        // `else if`: walk to the next link instead of recursing into execute,
+                        // which would allocate another environment per link
+                        Stmt::IfStmt { condition: c, then_: t, else_: e } => {
+                            if let Stmt::Located { line, .. } = else_branch {
+                                link_line = Some(*line);
+                            }
+                            (condition, then_, else_) = (c, t, e);
+                        }
+                        // Run with its location, so that its errors report its own line
+                        _ => break self.execute(else_branch, if_else, inside_loop),
+                    }
+                };
+                if let (Err(_), Some(line)) = (&result, link_line) {
+                    self.error_line.get_or_insert(line);
                 }
-                else if let Some(else_branch) = else_ {
-                    val = self.execute(else_branch, if_else, inside_loop)?;
+                result
+            }
+            Stmt::While { condition, body, else_ } => {
+                // `inside_loop` is about the enclosing loop, which is what the `else` branch runs in
+                let loop_env = Rc::new(RefCell::new(Environment::loop_enclosed_by(Rc::clone(&rc_env))));
+                match (self.run_while(condition, body, &rc_env, loop_env)?, else_) {
+                    // A loop statement has no use for the value it broke with
+                    (Value::Break(_), _) => Ok(Value::Nil),
+                    (ret @ Value::Return(_), _) => Ok(ret),
+                    // The loop ran to completion
+                    (_, Some(else_branch)) => self.execute(else_branch, rc_env, inside_loop),
+                    (val, None) => Ok(val),
                 }
-                Ok(val)
+            },
+            Stmt::ForIn { index, name, iterable, body } => {
+                let items: Vec<Value> = match iterable.eval(&rc_env, self)? {
+                    Value::Array(items) => items.borrow().clone(),
+                    Value::String(s) if index.is_none() => s.chars().map(Value::Char).collect(),
+                    other if index.is_some() => return Err(EvalError::NotIndexable(other.type_name())),
+                    other => return Err(EvalError::NotIterable(other.type_name())),
+                };
+                for (i, item) in items.into_iter().enumerate() {
+                    // A fresh scope for every element
+                    let iteration_env = Rc::new(RefCell::new(Environment::loop_enclosed_by(Rc::clone(&rc_env))));
+                    if let Some(index) = index {
+                        iteration_env.define(index, Value::Double(i as f64));
+                    }
+                    iteration_env.define(name, item);
+                    match self.execute(body, iteration_env, true)? {
+                        Value::Break(_) => break,
+                        ret @ Value::Return(_) => return Ok(ret),
+                        Value::Nil => {}
+                        val => self.echo(&val),
+                    }
+                }
+                Ok(Value::Nil)
             }
-            Stmt::While { condition, body } => {
-                let mut val = Value::Nil;
-                let loop_env = Rc::new(inside_env);
-                // BUG : ASsertions fail when while is inside a scope
-                assert!(inside_loop);
-                assert!(loop_env.borrow().in_loop());
-                while condition.eval(&Rc::clone(&rc_env),self)?.is_truthy() {
-                    val = self.execute(&body.as_ref(), Rc::clone(&loop_env), true)?;
-                    if matches!(val, Value::Break) {
-                        return Ok(Default::default());
+            Stmt::Repeat { count, body } => {
+                let times = match count.eval(&rc_env, self)? {
+                    Value::Double(n) if n >= 0.0 && n.fract() == 0.0 => n as usize,
+                    Value::Double(n) => return Err(EvalError::InvalidRepeatCount(n.to_string())),
+                    other => return Err(EvalError::InvalidRepeatCount(format!("a {}", other.type_name()))),
+                };
+                let loop_env = Rc::new(RefCell::new(Environment::loop_enclosed_by(Rc::clone(&rc_env))));
+                for _ in 0..times {
+                    match self.execute(body, Rc::clone(&loop_env), true)? {
+                        Value::Break(_) => break,
+                        ret @ Value::Return(_) => return Ok(ret),
+                        Value::Nil => {}
+                        val => self.echo(&val),
                     }
                 }
-                Ok(val)
-            },
+                Ok(Value::Nil)
+            }
             Stmt::VarDecl { name, initializer } => {
                 // let init_err : Option<EvalError> = None;
                 let val = if let Some(expr) = initializer {
-                    match expr.eval(&mut Rc::clone(&rc_env),self) {
-                        Ok(v) => v,
-                        Err(eval_err) => {
-                            loc!();
-                            eprintln!("{} {eval_err}", "Interpreter Error:".red());
-                            return Err(eval_err);
-                        }
-                    }
+                    expr.eval(&rc_env, self)?
                 } else {
                     Value::Nil
                 };
-                println!("var {name} declared to {}", val);
-                rc_env.define(name, val);
+                let line = format!("var {name} declared to {}", self.format_value(&val));
+                self.announce(&line);
+                if initializer.is_some() {
+                    rc_env.define(name, val.clone());
+                } else {
+                    rc_env.declare(name);
+                }
                 crate::loc!(format!("{:?}", self.env.borrow().values));
+                // The REPL echoes the declared value, files stay quiet
+                Ok(if self.repl && !self.raw_output { val } else { Value::Nil })
+            }
+            Stmt::Located { line, stmt, .. } => {
+                // Any earlier error has been dealt with by now, an inner statement that fails sets it again
+                self.error_line = None;
+                if self.trace {
+                    self.trace(&format!("[line {line}] {stmt}"));
+                }
+                let result = self.execute(stmt, rc_env, inside_loop);
+                if result.is_err() {
+                    self.error_line.get_or_insert(*line);
+                }
+                result
+            }
+            Stmt::TryCatch { try_block, error_name, catch_block } => {
+                self.try_depth += 1;
+                let result = self.execute(try_block, Rc::clone(&rc_env), inside_loop);
+                self.try_depth -= 1;
+                match result {
+                    Err(err) => {
+                        let catch_env = Rc::new(inside_env);
+                        // The error as raised, without where it sits in the expression. The message a
+                        // user gave to `error()` shouldn't depend on the parentheses around the call
+                        catch_env.define(error_name, Value::String(err.innermost().to_string().into()));
+                        self.execute(catch_block, catch_env, inside_loop)
+                    }
+                    ok => ok,
+                }
+            }
+            Stmt::Switch { discriminant, cases, default } => {
+                let value = discriminant.eval(&rc_env, self)?;
+                let mut body = default.as_ref();
+                for (case, stmts) in cases {
+                    if case.eval(&rc_env, self)?.is_equal(&value) {
+                        body = Some(stmts);
+                        break;
+                    }
+                }
+                match body {
+                    // `break` only leaves the switch, not an enclosing loop
+                    Some(stmts) => match self.execute_block(stmts, Rc::new(inside_env), true)? {
+                        Value::Break(_) => Ok(Value::Nil),
+                        val => Ok(val),
+                    },
+                    None => Ok(Value::Nil),
+                }
+            }
+            Stmt::ConstDecl { name, initializer } => {
+                let val = initializer.eval(&rc_env, self)?;
+                let line = format!("const {name} declared to {}", self.format_value(&val));
+                self.announce(&line);
+                rc_env.define_const(name, val.clone());
+                Ok(if self.repl && !self.raw_output { val } else { Value::Nil })
+            }
+            Stmt::Destructure { names, initializer } => {
+                let elements = match initializer.eval(&rc_env, self)? {
+                    Value::Array(elements) => elements.borrow().clone(),
+                    other => return Err(EvalError::DestructureNonArray(other.type_name())),
+                };
+                if elements.len() != names.len() {
+                    return Err(EvalError::DestructureMismatch(names.len(), elements.len()));
+                }
+                for (name, val) in names.iter().zip(elements) {
+                    let line = format!("var {name} declared to {}", self.format_value(&val));
+                    self.announce(&line);
+                    rc_env.define(name, val);
+                }
                 Ok(Value::Nil)
             }
-            Stmt::Break => if !inside_loop {
+            Stmt::Break(value) => if !inside_loop {
                 Err(EvalError::BreakWithout)
             } else {
-                Ok(Value::Break)
+                let value = match value {
+                    Some(expr) => expr.eval(&rc_env, self)?,
+                    None => Value::Nil,
+                };
+                Ok(Value::Break(Box::new(value)))
+            },
+            Stmt::Return(value) => if !self.in_function() {
+                Err(EvalError::ReturnOutsideFunction)
+            } else {
+                let value = match value {
+                    Some(expr) => expr.eval(&rc_env, self)?,
+                    None => Value::Nil,
+                };
+                Ok(Value::Return(Box::new(value)))
             },
-            Stmt::FunDecl { ident, params, body } => {
+            Stmt::FunDecl { ident, params, defaults, body } => {
                 let stack_env = Rc::new(inside_env);
                 let mut fn_params = vec![];
                 for param in params {
@@ -207,84 +627,284 @@ impl Interpreter {
                         fn_params.push(ident.to_owned());
                     }
                 }
-                let lox_fn = LoxFunction { stack_env , ident: ident.to_owned(), arity: params.len(), body : body.clone(), params : fn_params};
+                let lox_fn = LoxFunction { stack_env , ident: ident.to_owned(), arity: params.len(), body : body.clone(), params : fn_params, defaults: defaults.clone() };
                 rc_env.define(&ident.lexeme, Value::Function(Rc::new(lox_fn)));
-                println!("fn declared <{}>", ident.lexeme);
+                self.announce(&format!("fn declared <{}>", ident.lexeme));
                 Ok(Value::Nil)
             },
         }
     }
-    pub fn interpret(&mut self) -> () {
-        let mut stmts = self.stmts.clone();
-        for stmt in stmts.iter_mut() {
-            let val: ValueResult = match stmt {
-                // top level expr statements should be executed in global scope
-                expr_stmt @ Stmt::ExprStmt(_) => self.execute(expr_stmt, Rc::clone(&self.env), false),
-                    Stmt::Print(e) => e.eval(&Rc::clone(&self.env),self),
-                    Stmt::ErrStmt { message } => {
-                        loc!("Err stmt was printed");
-                        eprintln!(
-                            "{}{}{message}",
-                            "Interpreter Error: ".red(),
-                            "Bad statement ".yellow()
-                        );
-                        Ok(Value::Nil)
-                    }
-                    Stmt::Empty => Ok(Value::Nil),
-                    Stmt::Block(scoped_stmts) => self.execute_block(
-                        scoped_stmts,
-                        Rc::new(RefCell::new(Environment::enclosed_by(Rc::clone(&self.env)))),
-                        false
-                    ),
-                    // fancy @ syntax
-                    ifstmt @ Stmt::IfStmt {
-                        condition: _,
-                        then_: _,
-                        else_: _,
-                    } => {
-                        self.execute(&ifstmt, Rc::clone(&self.env), false)
-                    }
-                ,
-                // Declarations should produce no values
-                Stmt::VarDecl { name, initializer } => {
-                    // let init_err : Option<EvalError> = None;
-                    let val = if let Some(expr) = initializer {
-                        match expr.eval(&Rc::clone(&self.env),self) {
-                            Ok(v) => v,
-                            Err(eval_err) => {
-                                loc!();
-                                eprintln!("{} {eval_err}", "Interpreter Error:".red());
-                                continue;
-                            }
-                        }
-                    } else {
-                        Value::Nil
-                    };
-                    println!("var {name} declared to {}", val);
-                    self.env.define(name, val);
-                    crate::loc!(format!("{:?}", self.env.borrow().values));
-                    Ok(Value::Nil)
-                }
-                while_stmt @ Stmt::While { condition: _, body: _ } => {
-                    self.execute(&while_stmt, Rc::clone(&self.env), true)
-                },
-                Stmt::Break => {
-                    Err(EvalError::BreakWithout)
-                },
-                fn_decl @ Stmt::FunDecl { .. } => self.execute(fn_decl, Rc::clone(&self.env), false),
-                
-            };
-            match val {
+    /// Run the statements added since the last run, reporting runtime errors as they happen. A failing
+    /// statement doesn't stop the ones after it, the first error is returned once all of them have run
+    pub fn interpret(&mut self) -> Result<(), EvalError> {
+        let mut first_error = None;
+        let stmts = self.stmts[self.previous..].to_vec();
+        for stmt in stmts.iter() {
+            match self.run_stmt(stmt) {
                 Ok(val) => {
                     if val != Value::Nil {
-                        println!(">> {}", val);
+                        self.echo(&val);
                     }
                 }
                 Err(e) => {
                     loc!();
-                    eprintln!("{} {e}", "Interpreter Error:".red());
+                    self.report_error(&e);
+                    first_error.get_or_insert(e);
                 }
             };
         }
+        self.previous = self.stmts.len();
+        match first_error {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+    /// Execute already parsed `stmts` against the current environment, without adding them to the
+    /// interpreter's own statements. Stops at the first error, otherwise returns the value the last
+    /// statement produced
+    pub fn run_stmts(&mut self, stmts: &[Stmt]) -> ValueResult {
+        let mut last = Value::Nil;
+        for stmt in stmts {
+            last = self.run_stmt(stmt)?;
+            if last != Value::Nil {
+                self.echo(&last);
+            }
+        }
+        Ok(last)
+    }
+    /// Like [run_stmts](Interpreter::run_stmts), except that a trailing expression statement is not
+    /// echoed, its value is only returned
+    pub fn eval_stmts(&mut self, stmts: &[Stmt]) -> ValueResult {
+        match stmts.split_last() {
+            Some((last, rest)) if matches!(last.inner(), Stmt::ExprStmt(_)) => {
+                self.run_stmts(rest)?;
+                self.run_stmt(last)
+            }
+            _ => self.run_stmts(stmts),
+        }
+    }
+    /// Execute a single top level statement
+    fn run_stmt(&mut self, stmt: &Stmt) -> ValueResult {
+        match stmt {
+            // top level expr statements should be executed in global scope
+            expr_stmt @ Stmt::ExprStmt(_) => self.execute(expr_stmt, Rc::clone(&self.env), false),
+                print @ Stmt::Print(_) => self.execute(print, Rc::clone(&self.env), false),
+                Stmt::ErrStmt { message } => {
+                    loc!("Err stmt was reported");
+                    self.report_error(&EvalError::BadStatement(message.clone()));
+                    Ok(Value::Nil)
+                }
+                Stmt::Empty => Ok(Value::Nil),
+                Stmt::Block(scoped_stmts) => self.execute_block(
+                    scoped_stmts,
+                    Rc::new(RefCell::new(Environment::enclosed_by(Rc::clone(&self.env)))),
+                    false
+                ),
+                // fancy @ syntax
+                ifstmt @ Stmt::IfStmt {
+                    condition: _,
+                    then_: _,
+                    else_: _,
+                } => {
+                    self.execute(&ifstmt, Rc::clone(&self.env), false)
+                }
+            ,
+            // Declarations should produce no values
+            Stmt::VarDecl { name, initializer } => {
+                // let init_err : Option<EvalError> = None;
+                let val = if let Some(expr) = initializer {
+                    expr.eval(&Rc::clone(&self.env),self)?
+                } else {
+                    Value::Nil
+                };
+                let line = format!("var {name} declared to {}", self.format_value(&val));
+                self.announce(&line);
+                if initializer.is_some() {
+                    self.env.define(name, val.clone());
+                } else {
+                    self.env.declare(name);
+                }
+                crate::loc!(format!("{:?}", self.env.borrow().values));
+                // The REPL echoes the declared value, files stay quiet
+                Ok(if self.repl && !self.raw_output { val } else { Value::Nil })
+            }
+            repeat @ Stmt::Repeat { .. } => self.execute(repeat, Rc::clone(&self.env), false),
+            for_in @ Stmt::ForIn { .. } => self.execute(for_in, Rc::clone(&self.env), false),
+            Stmt::Located { line, stmt, .. } => {
+                self.error_line = None;
+                if self.trace {
+                    self.trace(&format!("[line {line}] {stmt}"));
+                }
+                let result = self.run_stmt(stmt);
+                if result.is_err() {
+                    self.error_line.get_or_insert(*line);
+                }
+                result
+            }
+            try_stmt @ Stmt::TryCatch { .. } => self.execute(try_stmt, Rc::clone(&self.env), false),
+            switch @ Stmt::Switch { .. } => self.execute(switch, Rc::clone(&self.env), false),
+            const_decl @ Stmt::ConstDecl { .. } => self.execute(const_decl, Rc::clone(&self.env), false),
+            destructure @ Stmt::Destructure { .. } => {
+                self.execute(destructure, Rc::clone(&self.env), false)
+            }
+            while_stmt @ Stmt::While { .. } => {
+                self.execute(&while_stmt, Rc::clone(&self.env), false)
+            },
+            Stmt::Break(_) => {
+                Err(EvalError::BreakWithout)
+            },
+            Stmt::Return(_) => Err(EvalError::ReturnOutsideFunction),
+            fn_decl @ Stmt::FunDecl { .. } => self.execute(fn_decl, Rc::clone(&self.env), false),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parser::traits::lox_callable::LoxCallable;
+    use crate::tokenizer::token_type::TokenType;
+    use crate::Lox;
+
+    fn ident(name: &str) -> Token {
+        Token::new(TokenType::IDENTIFIER, name.into(), 1, 1)
+    }
+
+    #[test]
+    fn reset_globals_keeps_natives() {
+        let mut lox = Lox::new(String::new());
+        lox.run(Some("var a = 1;".into())).unwrap();
+        let interpreter = &mut lox.repl_interpreter;
+        assert_eq!(interpreter.globals.get(&ident("a")), Ok(ResolvedVar::Defined(Value::Double(1.0))));
+        interpreter.reset_globals();
+        assert!(interpreter.globals.get(&ident("a")).is_err());
+        assert!(matches!(interpreter.globals.get(&ident("clock")), Ok(ResolvedVar::Defined(Value::Function(_)))));
+    }
+
+    #[test]
+    fn nil_is_not_uninitialized() {
+        let globals = Rc::new(RefCell::new(Environment::default()));
+        globals.define("a", Value::Nil);
+        globals.declare("b");
+        let scope = Rc::new(RefCell::new(Environment::enclosed_by(Rc::clone(&globals))));
+        assert_eq!(scope.get(&ident("a")), Ok(ResolvedVar::Defined(Value::Nil)));
+        assert_eq!(scope.get(&ident("b")), Ok(ResolvedVar::Uninitialized));
+        assert!(matches!(scope.get(&ident("c")), Err(RuntimeError::UncaughtReference(..))));
+        globals.put("b", Value::Nil).unwrap();
+        assert_eq!(scope.get(&ident("b")), Ok(ResolvedVar::Defined(Value::Nil)));
+    }
+
+    #[test]
+    fn constructors_share_globals() {
+        let names = |interpreter: &Interpreter| {
+            let mut names: Vec<String> = interpreter.globals.borrow().values.keys().cloned().collect();
+            names.sort();
+            names
+        };
+        let parser = Parser::new(vec![Token::new(TokenType::EOF, String::new(), 1, 1)]);
+        let from_new = names(&Interpreter::new(parser));
+        assert!(from_new.contains(&"clock".to_string()));
+        assert_eq!(from_new, names(&Interpreter::default()));
+    }
+
+    /// Counts how many times it's called
+    #[derive(Debug, derive_more::Display)]
+    #[display(fmt = "<native fn: side_effect>")]
+    struct SideEffect(Rc<std::cell::Cell<usize>>);
+
+    impl LoxCallable for SideEffect {
+        fn call(&self, _args: Vec<Value>, _interpreter: &mut Interpreter) -> ValueResult {
+            self.0.set(self.0.get() + 1);
+            Ok(Value::Bool(true))
+        }
+        fn arity(&self) -> usize {
+            0
+        }
+    }
+
+    /// Run `src` with a `sideEffect` native defined and return how many times it was called
+    fn side_effects(src: &str) -> usize {
+        let mut lox = Lox::new(src.into());
+        let mut scanner = crate::tokenizer::scanner::Scanner::new(src, &mut lox);
+        scanner.scan_tokens();
+        let mut interpreter = Interpreter::new(Parser::new(scanner.tokens));
+        interpreter.set_output(SharedBuffer::default());
+        interpreter.set_error_output(SharedBuffer::default());
+        let count = Rc::new(std::cell::Cell::new(0));
+        interpreter.globals.define("sideEffect", Value::Function(Rc::new(SideEffect(Rc::clone(&count)))));
+        let _ = interpreter.interpret();
+        count.get()
+    }
+
+    #[test]
+    fn error_handler_receives_uncaught_errors() {
+        let src = "print 1 / 0; try { error(\"caught\"); } catch (e) {} print 2;";
+        let mut lox = Lox::new(src.into());
+        let mut scanner = crate::tokenizer::scanner::Scanner::new(src, &mut lox);
+        scanner.scan_tokens();
+        let mut interpreter = Interpreter::new(Parser::new(scanner.tokens));
+        let (output, error_output) = (SharedBuffer::default(), SharedBuffer::default());
+        interpreter.set_output(output.clone());
+        interpreter.set_error_output(error_output.clone());
+        let errors = Rc::new(RefCell::new(vec![]));
+        let collected = Rc::clone(&errors);
+        interpreter.set_error_handler(move |err| {
+            assert!(matches!(err, EvalError::DivideByZero(_)), "{err}");
+            collected.borrow_mut().push(err.to_string());
+        });
+        let _ = interpreter.interpret();
+        assert_eq!(*errors.borrow(), ["Cannot divide by zero in: 1 / 0"]);
+        assert_eq!(interpreter.errors_reported(), 1);
+        assert_eq!(error_output.contents(), "");
+        assert_eq!(output.contents(), ">> 2\n");
+    }
+
+    #[test]
+    fn bad_statements_go_to_the_error_output() {
+        let src = "print 1 +; print 2;";
+        let mut lox = Lox::new(src.into());
+        let mut scanner = crate::tokenizer::scanner::Scanner::new(src, &mut lox);
+        scanner.scan_tokens();
+        let mut interpreter = Interpreter::new(Parser::new(scanner.tokens));
+        let (output, error_output) = (SharedBuffer::default(), SharedBuffer::default());
+        interpreter.set_output(output.clone());
+        interpreter.set_error_output(error_output.clone());
+        let _ = interpreter.interpret();
+        assert!(error_output.contents().contains("Bad statement"), "{}", error_output.contents());
+        assert_eq!(interpreter.errors_reported(), 1);
+        assert_eq!(output.contents(), ">> 2\n");
+    }
+
+    #[test]
+    fn logical_operators_short_circuit() {
+        assert_eq!(side_effects("print false and sideEffect();"), 0);
+        assert_eq!(side_effects("print true or sideEffect();"), 0);
+        assert_eq!(side_effects("print true and sideEffect();"), 1);
+        assert_eq!(side_effects("print false or sideEffect();"), 1);
+    }
+
+    #[test]
+    fn errors_in_calls_print_a_traceback() {
+        let src = "fun b() {\n  print 1 / 0;\n}\nfun a() {\n  b();\n}\na();\nprint 2;";
+        let mut lox = Lox::new(src.into());
+        let mut scanner = crate::tokenizer::scanner::Scanner::new(src, &mut lox);
+        scanner.scan_tokens();
+        let mut interpreter = Interpreter::new(Parser::new(scanner.tokens));
+        interpreter.set_output(SharedBuffer::default());
+        let errors = SharedBuffer::default();
+        interpreter.set_error_output(errors.clone());
+        interpreter.interpret().unwrap();
+        let errors = errors.contents();
+        let trace: Vec<&str> = errors.lines().skip(1).collect();
+        assert_eq!(trace, ["  in b(), called at line 5", "  in a(), called at line 7"], "{errors}");
+        assert!(interpreter.call_stack.is_empty());
+    }
+
+    #[test]
+    fn arguments_stop_at_first_error() {
+        assert_eq!(side_effects("print len(1 / 0, sideEffect());"), 0);
+        assert_eq!(side_effects("print len(-nil, sideEffect());"), 0);
+        // arguments before the failing one still run, left to right
+        assert_eq!(side_effects("print len(sideEffect(), 1 / 0, sideEffect());"), 1);
     }
 }
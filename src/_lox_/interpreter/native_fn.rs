@@ -1,8 +1,17 @@
-use crate::parser::{error::EvalError, traits::lox_callable::LoxCallable, value::Value};
+use crate::parser::{
+    error::EvalError,
+    traits::lox_callable::LoxCallable,
+    value::{Falsy, HashKey, NumberFormat, Value, ValueResult},
+};
 use derive_more::Display;
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::rc::Rc;
 use std::time::{SystemTime, UNIX_EPOCH};
 
-use super::Interpreter;
+use super::{Interpreter, Memory, ResolvedVar};
+use crate::tokenizer::{token::Token, token_type::TokenType};
 #[derive(Debug, Display)]
 #[display(fmt = "<native fn: clock>")]
 pub struct Clock;
@@ -14,12 +23,7 @@ impl LoxCallable for Clock {
         _interpreter: &mut Interpreter
     ) -> crate::parser::value::ValueResult {
         if args.len() != 0 {
-            crate::Lox::report_runtime_err(format!(
-                "Expected {} but got {} arguments",
-                self.arity(),
-                args.len()
-            ));
-            return Err(EvalError::FunctionArgError);
+            return Err(EvalError::ArityMismatch(self.arity(), args.len()));
         } else {
             Ok(Value::Double(
                 SystemTime::now()
@@ -33,3 +37,1313 @@ impl LoxCallable for Clock {
         0
     }
 }
+
+/// A small splitmix64 generator, good enough for games and simulations.
+/// Lives on the [Interpreter] so that `seed(n)` makes every following
+/// `random()`/`random_int()` call reproducible
+#[derive(Debug, Clone)]
+pub struct Prng {
+    state: u64,
+}
+impl Prng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+    /// Seed from the system clock, used when the program never calls `seed`
+    pub fn from_time() -> Self {
+        Self::new(
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .expect("system time before epoch")
+                .as_nanos() as u64,
+        )
+    }
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+    /// A float in [0, 1) built from the top 53 bits
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// Extract a whole number from `value`, `what` names the argument in the error message
+fn whole_number(value: &Value, what: &str) -> Result<f64, EvalError> {
+    match value.is_numeric() {
+        Some(n) if n.fract() == 0.0 => Ok(n),
+        _ => Err(EvalError::InvalidArgument(format!("{what} must be a whole number"))),
+    }
+}
+
+#[derive(Debug, Display)]
+#[display(fmt = "<native fn: random>")]
+pub struct Random;
+
+impl LoxCallable for Random {
+    fn call(&self, _args: Vec<Value>, interpreter: &mut Interpreter) -> ValueResult {
+        Ok(Value::Double(interpreter.rng.next_f64()))
+    }
+    fn arity(&self) -> usize {
+        0
+    }
+}
+
+#[derive(Debug, Display)]
+#[display(fmt = "<native fn: random_int>")]
+pub struct RandomInt;
+
+impl LoxCallable for RandomInt {
+    /// Returns a whole number in the inclusive range [lo, hi]
+    fn call(&self, args: Vec<Value>, interpreter: &mut Interpreter) -> ValueResult {
+        let lo = whole_number(&args[0], "random_int lower bound")?;
+        let hi = whole_number(&args[1], "random_int upper bound")?;
+        if lo > hi {
+            return Err(EvalError::InvalidArgument(format!(
+                "random_int lower bound {lo} is greater than upper bound {hi}"
+            )));
+        }
+        // `as` saturates, so a range too wide for a u64 comes out as u64::MAX and fails the add
+        let span = ((hi - lo) as u64).checked_add(1).ok_or_else(|| {
+            EvalError::InvalidArgument(format!("random_int range from {lo} to {hi} is too wide"))
+        })?;
+        Ok(Value::Double(lo + (interpreter.rng.next_u64() % span) as f64))
+    }
+    fn arity(&self) -> usize {
+        2
+    }
+}
+
+#[derive(Debug, Display)]
+#[display(fmt = "<native fn: seed>")]
+pub struct Seed;
+
+impl LoxCallable for Seed {
+    fn call(&self, args: Vec<Value>, interpreter: &mut Interpreter) -> ValueResult {
+        let seed = whole_number(&args[0], "seed")?;
+        interpreter.rng = Prng::new(seed as i64 as u64);
+        Ok(Value::Nil)
+    }
+    fn arity(&self) -> usize {
+        1
+    }
+}
+
+#[derive(Debug, Display)]
+#[display(fmt = "<native fn: set_number_format>")]
+pub struct SetNumberFormat;
+
+impl LoxCallable for SetNumberFormat {
+    /// `set_number_format(mode, precision)` where mode is one of "shortest", "fixed" or "scientific".
+    /// precision is ignored for "shortest", required for "fixed" and optional (nil) for "scientific"
+    fn call(&self, args: Vec<Value>, interpreter: &mut Interpreter) -> ValueResult {
+        let precision = match &args[1] {
+            Value::Nil => None,
+            p => Some(whole_number(p, "number format precision")?.max(0.0) as usize),
+        };
+        let format = match (args[0].is_string().as_deref(), precision) {
+            (Some("shortest"), _) => NumberFormat::Shortest,
+            (Some("fixed"), Some(precision)) => NumberFormat::Fixed(precision),
+            (Some("fixed"), None) => {
+                return Err(EvalError::InvalidArgument(
+                    "fixed number format needs a precision".into(),
+                ))
+            }
+            (Some("scientific"), precision) => NumberFormat::Scientific(precision),
+            _ => {
+                return Err(EvalError::InvalidArgument(
+                    "number format must be one of \"shortest\", \"fixed\" or \"scientific\"".into(),
+                ))
+            }
+        };
+        interpreter.number_format = format;
+        Ok(Value::Nil)
+    }
+    fn arity(&self) -> usize {
+        2
+    }
+}
+
+#[derive(Debug, Display)]
+#[display(fmt = "<native fn: set_falsy>")]
+pub struct SetFalsy;
+
+impl LoxCallable for SetFalsy {
+    /// `set_falsy(kinds)` makes conditions treat the values in `kinds`, an array of "empty_string",
+    /// "zero" and "empty_array", as false. Replaces earlier calls, `set_falsy([])` goes back to only
+    /// `false` and `nil`
+    fn call(&self, args: Vec<Value>, interpreter: &mut Interpreter) -> ValueResult {
+        let kinds = match &args[0] {
+            Value::Array(kinds) => kinds.borrow().clone(),
+            other => {
+                return Err(EvalError::InvalidArgument(format!(
+                    "set_falsy expects an array of value kinds, got {}",
+                    other.type_name()
+                )))
+            }
+        };
+        let mut falsy = Falsy::default();
+        for kind in kinds {
+            match kind.is_string().as_deref() {
+                Some("empty_string") => falsy.empty_string = true,
+                Some("zero") => falsy.zero = true,
+                Some("empty_array") => falsy.empty_array = true,
+                _ => {
+                    return Err(EvalError::InvalidArgument(format!(
+                        "falsy kind must be one of \"empty_string\", \"zero\" or \"empty_array\", got {kind}"
+                    )))
+                }
+            }
+        }
+        interpreter.falsy = falsy;
+        Ok(Value::Nil)
+    }
+    fn arity(&self) -> usize {
+        1
+    }
+}
+
+#[derive(Debug, Display)]
+#[display(fmt = "<native fn: char_at>")]
+pub struct CharAt;
+
+impl LoxCallable for CharAt {
+    /// `char_at(string, index)` returns the char at `index`, counting unicode scalar values from 0
+    fn call(&self, args: Vec<Value>, _interpreter: &mut Interpreter) -> ValueResult {
+        let string = args[0].is_string().ok_or_else(|| {
+            EvalError::InvalidArgument(format!("char_at expects a string, got {}", args[0].type_name()))
+        })?;
+        let index = whole_number(&args[1], "char_at index")?;
+        let len = string.chars().count();
+        if index < 0.0 || index as usize >= len {
+            return Err(EvalError::InvalidArgument(format!(
+                "char_at index {index} out of range for string of length {len}"
+            )));
+        }
+        Ok(Value::Char(string.chars().nth(index as usize).expect("index checked above")))
+    }
+    fn arity(&self) -> usize {
+        2
+    }
+}
+
+#[derive(Debug, Display)]
+#[display(fmt = "<native fn: len>")]
+pub struct Len;
+
+impl LoxCallable for Len {
+    /// Number of chars in a string, elements in an array or bytes in bytes
+    fn call(&self, args: Vec<Value>, _interpreter: &mut Interpreter) -> ValueResult {
+        match &args[0] {
+            Value::String(s) => Ok(Value::Double(s.chars().count() as f64)),
+            Value::Array(items) => Ok(Value::Double(items.borrow().len() as f64)),
+            Value::Bytes(bytes) => Ok(Value::Double(bytes.len() as f64)),
+            other => Err(EvalError::InvalidArgument(format!(
+                "len expects a string, an array or bytes, got {}",
+                other.type_name()
+            ))),
+        }
+    }
+    fn arity(&self) -> usize {
+        1
+    }
+}
+
+#[derive(Debug, Display)]
+#[display(fmt = "<native fn: contains>")]
+pub struct Contains;
+
+impl LoxCallable for Contains {
+    /// `contains(string, substring_or_char)` or `contains(array, element)`
+    fn call(&self, args: Vec<Value>, _interpreter: &mut Interpreter) -> ValueResult {
+        match (&args[0], &args[1]) {
+            (Value::String(s), Value::String(needle)) => Ok(Value::Bool(s.contains(&**needle))),
+            (Value::String(s), Value::Char(c)) => Ok(Value::Bool(s.contains(*c))),
+            (Value::String(_), other) => Err(EvalError::InvalidArgument(format!(
+                "cannot search a string for a {}",
+                other.type_name()
+            ))),
+            (Value::Array(items), item) => Ok(Value::Bool(items.borrow().contains(item))),
+            (other, _) => Err(EvalError::InvalidArgument(format!(
+                "contains expects a string or an array, got {}",
+                other.type_name()
+            ))),
+        }
+    }
+    fn arity(&self) -> usize {
+        2
+    }
+}
+
+/// `value` as a string, otherwise an error naming the argument `what`
+fn string_arg(value: &Value, what: &str) -> Result<String, EvalError> {
+    String::try_from(value)
+        .map_err(|_| EvalError::InvalidArgument(format!("{what} must be a string, got {}", value.type_name())))
+}
+
+/// `value` as a number, otherwise an error naming the argument `what`
+fn number_arg(value: &Value, what: &str) -> Result<f64, EvalError> {
+    f64::try_from(value)
+        .map_err(|_| EvalError::InvalidArgument(format!("{what} must be a number, got {}", value.type_name())))
+}
+
+#[derive(Debug, Display)]
+#[display(fmt = "<native fn: starts_with>")]
+pub struct StartsWith;
+
+impl LoxCallable for StartsWith {
+    fn call(&self, args: Vec<Value>, _interpreter: &mut Interpreter) -> ValueResult {
+        let s = string_arg(&args[0], "starts_with string")?;
+        let prefix = string_arg(&args[1], "starts_with prefix")?;
+        Ok(Value::Bool(s.starts_with(&prefix)))
+    }
+    fn arity(&self) -> usize {
+        2
+    }
+}
+
+#[derive(Debug, Display)]
+#[display(fmt = "<native fn: ends_with>")]
+pub struct EndsWith;
+
+impl LoxCallable for EndsWith {
+    fn call(&self, args: Vec<Value>, _interpreter: &mut Interpreter) -> ValueResult {
+        let s = string_arg(&args[0], "ends_with string")?;
+        let suffix = string_arg(&args[1], "ends_with suffix")?;
+        Ok(Value::Bool(s.ends_with(&suffix)))
+    }
+    fn arity(&self) -> usize {
+        2
+    }
+}
+
+#[derive(Debug, Display)]
+#[display(fmt = "<native fn: trim>")]
+pub struct Trim;
+
+impl LoxCallable for Trim {
+    /// The string without leading and trailing whitespace
+    fn call(&self, args: Vec<Value>, _interpreter: &mut Interpreter) -> ValueResult {
+        Ok(Value::String(string_arg(&args[0], "trim argument")?.trim().into()))
+    }
+    fn arity(&self) -> usize {
+        1
+    }
+}
+
+#[derive(Debug, Display)]
+#[display(fmt = "<native fn: chars>")]
+pub struct Chars;
+
+impl LoxCallable for Chars {
+    /// An array of the string's chars, one per unicode scalar value
+    fn call(&self, args: Vec<Value>, _interpreter: &mut Interpreter) -> ValueResult {
+        let s = string_arg(&args[0], "chars argument")?;
+        Ok(s.chars().map(Value::Char).collect::<Vec<_>>().into())
+    }
+    fn arity(&self) -> usize {
+        1
+    }
+}
+
+#[derive(Debug, Display)]
+#[display(fmt = "<native fn: assert_eq>")]
+pub struct AssertEq;
+
+impl LoxCallable for AssertEq {
+    /// Nil if both arguments are equal, an [EvalError::AssertionFailed] showing both otherwise
+    fn call(&self, args: Vec<Value>, _interpreter: &mut Interpreter) -> ValueResult {
+        let (actual, expected) = (&args[0], &args[1]);
+        if actual == expected {
+            Ok(Value::Nil)
+        } else {
+            Err(EvalError::AssertionFailed(format!("expected {expected}, got {actual}")))
+        }
+    }
+    fn arity(&self) -> usize {
+        2
+    }
+}
+
+#[derive(Debug, Display)]
+#[display(fmt = "<native fn: assert_true>")]
+pub struct AssertTrue;
+
+impl LoxCallable for AssertTrue {
+    /// Nil if the argument is truthy, an [EvalError::AssertionFailed] otherwise
+    fn call(&self, args: Vec<Value>, interpreter: &mut Interpreter) -> ValueResult {
+        if args[0].is_truthy_in(interpreter) {
+            Ok(Value::Nil)
+        } else {
+            Err(EvalError::AssertionFailed(format!("{} is not truthy", args[0])))
+        }
+    }
+    fn arity(&self) -> usize {
+        1
+    }
+}
+
+#[derive(Debug, Display)]
+#[display(fmt = "<native fn: version>")]
+pub struct Version;
+
+impl LoxCallable for Version {
+    /// Version of the interpreter, as in Cargo.toml
+    fn call(&self, _args: Vec<Value>, _interpreter: &mut Interpreter) -> ValueResult {
+        Ok(Value::String(env!("CARGO_PKG_VERSION").into()))
+    }
+    fn arity(&self) -> usize {
+        0
+    }
+}
+
+#[derive(Debug, Display)]
+#[display(fmt = "<native fn: hex_encode>")]
+pub struct HexEncode;
+
+impl LoxCallable for HexEncode {
+    /// Lowercase hex string of bytes, two digits per byte
+    fn call(&self, args: Vec<Value>, _interpreter: &mut Interpreter) -> ValueResult {
+        match &args[0] {
+            Value::Bytes(bytes) => Ok(Value::String(bytes.iter().map(|b| format!("{b:02x}")).collect::<String>().into())),
+            other => Err(EvalError::InvalidArgument(format!(
+                "hex_encode expects bytes, got {}",
+                other.type_name()
+            ))),
+        }
+    }
+    fn arity(&self) -> usize {
+        1
+    }
+}
+
+#[derive(Debug, Display)]
+#[display(fmt = "<native fn: hex_decode>")]
+pub struct HexDecode;
+
+impl LoxCallable for HexDecode {
+    /// Bytes from a string of hex digit pairs, either case
+    fn call(&self, args: Vec<Value>, _interpreter: &mut Interpreter) -> ValueResult {
+        let hex = string_arg(&args[0], "hex_decode argument")?;
+        let invalid = || EvalError::InvalidArgument(format!("\"{hex}\" is not valid hex"));
+        // Every char is checked up front, `from_str_radix` alone would take a pair like "+f"
+        if hex.len() % 2 != 0 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(invalid());
+        }
+        let bytes = (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| invalid()))
+            .collect::<Result<Vec<u8>, _>>()?;
+        Ok(Value::Bytes(bytes))
+    }
+    fn arity(&self) -> usize {
+        1
+    }
+}
+
+#[derive(Debug, Display)]
+#[display(fmt = "<native fn: div>")]
+pub struct Div;
+
+impl LoxCallable for Div {
+    /// Floored division, `div(-7, 2)` is -4 where `-7 / 2` would be -3.5.
+    /// `//` can't be an operator since it starts a comment
+    fn call(&self, args: Vec<Value>, _interpreter: &mut Interpreter) -> ValueResult {
+        let (a, b) = match (args[0].is_numeric(), args[1].is_numeric()) {
+            (Some(a), Some(b)) => (a, b),
+            _ => {
+                return Err(EvalError::InvalidArgument(format!(
+                    "div expects two numbers, got {} and {}",
+                    args[0].type_name(),
+                    args[1].type_name()
+                )))
+            }
+        };
+        if b == 0.0 {
+            return Err(EvalError::InvalidArgument("div by zero".into()));
+        }
+        Ok(Value::Double((a / b).floor()))
+    }
+    fn arity(&self) -> usize {
+        2
+    }
+}
+
+#[derive(Debug, Display)]
+#[display(fmt = "<native fn: error>")]
+pub struct Raise;
+
+impl LoxCallable for Raise {
+    /// Raise a runtime error with `message`, a `catch` binds it as a string
+    fn call(&self, args: Vec<Value>, _interpreter: &mut Interpreter) -> ValueResult {
+        match &args[0] {
+            Value::String(message) => Err(EvalError::Raised(message.to_string())),
+            other => Err(EvalError::Raised(other.to_string())),
+        }
+    }
+    fn arity(&self) -> usize {
+        1
+    }
+}
+
+#[derive(Debug, Display)]
+#[display(fmt = "<native fn: int>")]
+pub struct Int;
+
+impl LoxCallable for Int {
+    /// Truncate a number towards zero, or parse a string holding a whole number.
+    /// Lox has no integer type so the result is a whole `Value::Double`
+    fn call(&self, args: Vec<Value>, _interpreter: &mut Interpreter) -> ValueResult {
+        match &args[0] {
+            Value::Double(n) if n.is_finite() => Ok(Value::Double(n.trunc())),
+            Value::String(s) => match s.trim().parse::<i64>() {
+                Ok(n) => Ok(Value::Double(n as f64)),
+                Err(_) => Err(EvalError::InvalidArgument(format!("int cannot parse \"{s}\" as a whole number"))),
+            },
+            Value::Double(n) => Err(EvalError::InvalidArgument(format!("int cannot convert {n}"))),
+            other => Err(EvalError::InvalidArgument(format!(
+                "int expects a number or a string, got {}",
+                other.type_name()
+            ))),
+        }
+    }
+    fn arity(&self) -> usize {
+        1
+    }
+}
+
+#[derive(Debug, Display)]
+#[display(fmt = "<native fn: approx_eq>")]
+pub struct ApproxEq;
+
+impl LoxCallable for ApproxEq {
+    /// `approx_eq(a, b, epsilon)` is true when `a` and `b` are no more than `epsilon` apart
+    fn call(&self, args: Vec<Value>, _interpreter: &mut Interpreter) -> ValueResult {
+        match (args[0].is_numeric(), args[1].is_numeric(), args[2].is_numeric()) {
+            (Some(a), Some(b), Some(epsilon)) if epsilon >= 0.0 => Ok(Value::Bool((a - b).abs() <= epsilon)),
+            (Some(_), Some(_), Some(epsilon)) => Err(EvalError::InvalidArgument(format!(
+                "approx_eq tolerance must not be negative, got {epsilon}"
+            ))),
+            _ => Err(EvalError::InvalidArgument(format!(
+                "approx_eq expects three numbers, got {}, {} and {}",
+                args[0].type_name(),
+                args[1].type_name(),
+                args[2].type_name()
+            ))),
+        }
+    }
+    fn arity(&self) -> usize {
+        3
+    }
+}
+
+#[derive(Debug, Display)]
+#[display(fmt = "<native fn: abs>")]
+pub struct Abs;
+
+impl LoxCallable for Abs {
+    fn call(&self, args: Vec<Value>, _interpreter: &mut Interpreter) -> ValueResult {
+        Ok(Value::Double(number_arg(&args[0], "abs argument")?.abs()))
+    }
+    fn arity(&self) -> usize {
+        1
+    }
+}
+
+#[derive(Debug, Display)]
+#[display(fmt = "<native fn: sign>")]
+pub struct Sign;
+
+impl LoxCallable for Sign {
+    /// -1, 0 or 1 as the number is negative, zero or positive. NaN stays NaN
+    fn call(&self, args: Vec<Value>, _interpreter: &mut Interpreter) -> ValueResult {
+        let n = number_arg(&args[0], "sign argument")?;
+        let sign = if n.is_nan() {
+            n
+        } else if n == 0.0 {
+            0.0
+        } else {
+            n.signum()
+        };
+        Ok(Value::Double(sign))
+    }
+    fn arity(&self) -> usize {
+        1
+    }
+}
+
+#[derive(Debug, Display)]
+#[display(fmt = "<native fn: clamp>")]
+pub struct Clamp;
+
+impl LoxCallable for Clamp {
+    /// `clamp(x, lo, hi)` is `x` bounded to `[lo, hi]`, an error when `lo` is above `hi`
+    fn call(&self, args: Vec<Value>, _interpreter: &mut Interpreter) -> ValueResult {
+        let x = number_arg(&args[0], "clamp value")?;
+        let lo = number_arg(&args[1], "clamp lower bound")?;
+        let hi = number_arg(&args[2], "clamp upper bound")?;
+        // Also rejects NaN bounds, which f64::clamp would panic on
+        if !(lo <= hi) {
+            return Err(EvalError::InvalidArgument(format!(
+                "clamp lower bound {lo} must not be above upper bound {hi}"
+            )));
+        }
+        Ok(Value::Double(x.clamp(lo, hi)))
+    }
+    fn arity(&self) -> usize {
+        3
+    }
+}
+
+#[derive(Debug, Display)]
+#[display(fmt = "<native fn: globals>")]
+pub struct Globals;
+
+impl LoxCallable for Globals {
+    /// Sorted names of the globals defined so far, leaving out native functions
+    fn call(&self, _args: Vec<Value>, interpreter: &mut Interpreter) -> ValueResult {
+        let globals = interpreter.globals.borrow();
+        let mut names: Vec<&String> = globals
+            .values
+            .iter()
+            .filter(|(_, value)| !matches!(value, Value::Function(f) if f.is_native()))
+            .map(|(name, _)| name)
+            .collect();
+        names.sort();
+        Ok(names.into_iter().map(|name| Value::String(name.as_str().into())).collect::<Vec<_>>().into())
+    }
+    fn arity(&self) -> usize {
+        0
+    }
+}
+
+#[derive(Debug, Display)]
+#[display(fmt = "<native fn: get_or>")]
+pub struct GetOr;
+
+impl LoxCallable for GetOr {
+    /// Value of the global variable named by the first argument, or the second argument
+    /// if there's no such global or it hasn't been initialized
+    fn call(&self, args: Vec<Value>, interpreter: &mut Interpreter) -> ValueResult {
+        let name = match &args[0] {
+            Value::String(name) => name,
+            other => {
+                return Err(EvalError::InvalidArgument(format!(
+                    "get_or expects a variable name string, got {}",
+                    other.type_name()
+                )))
+            }
+        };
+        let ident = Token::new(TokenType::IDENTIFIER, name.to_string(), 0, 0);
+        match interpreter.globals.get(&ident) {
+            Ok(ResolvedVar::Defined(value)) => Ok(value),
+            _ => Ok(args[1].clone()),
+        }
+    }
+    fn arity(&self) -> usize {
+        2
+    }
+}
+
+#[derive(Debug, Display)]
+#[display(fmt = "<native fn: sort>")]
+pub struct Sort;
+
+impl LoxCallable for Sort {
+    /// `sort(array)` sorts numbers, strings or chars ascending, `sort(array, cmp)` orders by `cmp(a, b)`
+    /// which returns a negative number, zero or a positive number. Sorts in place and is stable
+    fn call(&self, mut args: Vec<Value>, interpreter: &mut Interpreter) -> ValueResult {
+        let comparator = if args.len() == 2 { args.pop() } else { None };
+        let array = match &args[0] {
+            Value::Array(array) => array,
+            other => {
+                return Err(EvalError::InvalidArgument(format!(
+                    "sort expects an array, got {}",
+                    other.type_name()
+                )))
+            }
+        };
+        // Sort a copy so the comparator is free to look at the array
+        let items = array.borrow().clone();
+        let sorted = match comparator.as_ref().map(Value::as_callable) {
+            None => merge_sort(items, &mut |a, b| {
+                a.partial_cmp(b).ok_or_else(|| {
+                    EvalError::InvalidArgument(format!(
+                        "cannot compare {} with {}",
+                        a.type_name(),
+                        b.type_name()
+                    ))
+                })
+            })?,
+            // Trailing parameters with defaults are fine, as with `reduce`
+            Some(Some(cmp)) if cmp.min_arity() <= 2 && cmp.arity() >= 2 => merge_sort(items, &mut |a, b| {
+                match cmp.call(vec![a.clone(), b.clone()], interpreter)? {
+                    Value::Double(n) if n < 0.0 => Ok(Ordering::Less),
+                    Value::Double(n) if n > 0.0 => Ok(Ordering::Greater),
+                    Value::Double(_) => Ok(Ordering::Equal),
+                    other => Err(EvalError::InvalidArgument(format!(
+                        "sort comparator must return a number, got {}",
+                        other.type_name()
+                    ))),
+                }
+            })?,
+            Some(_) => {
+                return Err(EvalError::InvalidArgument(
+                    "sort comparator must be a function of two arguments".into(),
+                ))
+            }
+        };
+        *array.borrow_mut() = sorted;
+        Ok(Value::Nil)
+    }
+    fn arity(&self) -> usize {
+        2
+    }
+    fn min_arity(&self) -> usize {
+        1
+    }
+}
+
+#[derive(Debug, Display)]
+#[display(fmt = "<native fn: reduce>")]
+pub struct Reduce;
+
+impl LoxCallable for Reduce {
+    /// `reduce(array, fn, initial)` folds the array from the left, starting with `initial` and
+    /// replacing the accumulator with `fn(acc, element)` for each element. An empty array gives `initial`
+    fn call(&self, args: Vec<Value>, interpreter: &mut Interpreter) -> ValueResult {
+        let items = match &args[0] {
+            Value::Array(array) => array.borrow().clone(),
+            other => {
+                return Err(EvalError::InvalidArgument(format!(
+                    "reduce expects an array, got {}",
+                    other.type_name()
+                )))
+            }
+        };
+        let f = match args[1].as_callable() {
+            Some(f) if f.min_arity() <= 2 && f.arity() >= 2 => f,
+            _ => {
+                return Err(EvalError::InvalidArgument(
+                    "reduce expects a function of two arguments".into(),
+                ))
+            }
+        };
+        let mut acc = args[2].clone();
+        for item in items {
+            acc = f.call(vec![acc, item], interpreter)?;
+        }
+        Ok(acc)
+    }
+    fn arity(&self) -> usize {
+        3
+    }
+}
+
+/// Stable merge sort with a fallible comparison, the first error aborts the sort.
+/// Unlike `slice::sort_by` it doesn't care whether a user comparator is a total order
+fn merge_sort(
+    mut items: Vec<Value>,
+    cmp: &mut impl FnMut(&Value, &Value) -> Result<Ordering, EvalError>,
+) -> Result<Vec<Value>, EvalError> {
+    if items.len() <= 1 {
+        return Ok(items);
+    }
+    let right = merge_sort(items.split_off(items.len() / 2), cmp)?;
+    let left = merge_sort(items, cmp)?;
+    let mut merged = Vec::with_capacity(left.len() + right.len());
+    let (mut left, mut right) = (left.into_iter().peekable(), right.into_iter().peekable());
+    while let (Some(l), Some(r)) = (left.peek(), right.peek()) {
+        if cmp(r, l)? == Ordering::Less {
+            merged.extend(right.next());
+        } else {
+            merged.extend(left.next());
+        }
+    }
+    merged.extend(left);
+    merged.extend(right);
+    Ok(merged)
+}
+
+/// Called by `exit(code)` with the requested status
+pub struct ExitHook(pub Box<dyn Fn(i32)>);
+
+impl Default for ExitHook {
+    fn default() -> Self {
+        Self(Box::new(|code| std::process::exit(code)))
+    }
+}
+
+impl std::fmt::Debug for ExitHook {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "ExitHook")
+    }
+}
+
+#[derive(Debug, Display)]
+#[display(fmt = "<native fn: exit>")]
+pub struct Exit;
+
+impl LoxCallable for Exit {
+    /// Flush program output and hand `code` to the interpreter's exit hook
+    fn call(&self, args: Vec<Value>, interpreter: &mut Interpreter) -> ValueResult {
+        let code = whole_number(&args[0], "exit code")?;
+        if code < i32::MIN as f64 || code > i32::MAX as f64 {
+            return Err(EvalError::InvalidArgument(format!("exit code {code} is out of range")));
+        }
+        interpreter.flush_output();
+        (interpreter.exit_hook.0)(code as i32);
+        Ok(Value::Nil)
+    }
+    fn arity(&self) -> usize {
+        1
+    }
+}
+
+#[derive(Debug, Display)]
+#[display(fmt = "<native fn: read_file>")]
+pub struct ReadFile;
+
+impl LoxCallable for ReadFile {
+    /// Contents of the file at `path` as a string
+    fn call(&self, args: Vec<Value>, _interpreter: &mut Interpreter) -> ValueResult {
+        let path = string_arg(&args[0], "read_file path")?;
+        match std::fs::read_to_string(&path) {
+            Ok(contents) => Ok(contents.into()),
+            Err(err) => Err(EvalError::IoError(format!("cannot read {path}: {err}"))),
+        }
+    }
+    fn arity(&self) -> usize {
+        1
+    }
+}
+
+#[derive(Debug, Display)]
+#[display(fmt = "<native fn: write_file>")]
+pub struct WriteFile;
+
+impl LoxCallable for WriteFile {
+    /// Write the string `contents` to the file at `path`, replacing the file if it exists
+    fn call(&self, args: Vec<Value>, _interpreter: &mut Interpreter) -> ValueResult {
+        let path = string_arg(&args[0], "write_file path")?;
+        let contents = string_arg(&args[1], "write_file contents")?;
+        match std::fs::write(&path, contents) {
+            Ok(()) => Ok(Value::Nil),
+            Err(err) => Err(EvalError::IoError(format!("cannot write {path}: {err}"))),
+        }
+    }
+    fn arity(&self) -> usize {
+        2
+    }
+}
+
+#[derive(Debug, Display)]
+#[display(fmt = "<native fn: read_line>")]
+pub struct ReadLine;
+
+impl LoxCallable for ReadLine {
+    /// Read a line of input without its line ending, nil at the end of input
+    fn call(&self, _args: Vec<Value>, interpreter: &mut Interpreter) -> ValueResult {
+        match interpreter.read_line() {
+            Ok(Some(line)) => Ok(Value::String(line.into())),
+            Ok(None) => Ok(Value::Nil),
+            Err(err) => Err(EvalError::IoError(format!("cannot read input: {err}"))),
+        }
+    }
+    fn arity(&self) -> usize {
+        0
+    }
+}
+
+type LoxMap = Rc<RefCell<HashMap<HashKey, Value>>>;
+
+/// The map a map native was called on, and the string key it was given
+fn map_args<'a>(args: &'a [Value], native: &str) -> Result<(&'a LoxMap, HashKey), EvalError> {
+    let map = match &args[0] {
+        Value::Map(map) => map,
+        other => {
+            return Err(EvalError::InvalidArgument(format!(
+                "{native} expects a map, got {}",
+                other.type_name()
+            )))
+        }
+    };
+    match &args[1] {
+        Value::String(key) => Ok((map, HashKey::String(Rc::clone(key)))),
+        other => Err(EvalError::InvalidArgument(format!(
+            "{native} expects a string key, got {}",
+            other.type_name()
+        ))),
+    }
+}
+
+#[derive(Debug, Display)]
+#[display(fmt = "<native fn: map>")]
+pub struct NewMap;
+
+impl LoxCallable for NewMap {
+    fn call(&self, _args: Vec<Value>, _interpreter: &mut Interpreter) -> ValueResult {
+        Ok(Value::Map(Rc::default()))
+    }
+    fn arity(&self) -> usize {
+        0
+    }
+}
+
+#[derive(Debug, Display)]
+#[display(fmt = "<native fn: has_key>")]
+pub struct HasKey;
+
+impl LoxCallable for HasKey {
+    fn call(&self, args: Vec<Value>, _interpreter: &mut Interpreter) -> ValueResult {
+        let (map, key) = map_args(&args, "has_key")?;
+        Ok(Value::Bool(map.borrow().contains_key(&key)))
+    }
+    fn arity(&self) -> usize {
+        2
+    }
+}
+
+#[derive(Debug, Display)]
+#[display(fmt = "<native fn: map_get>")]
+pub struct MapGet;
+
+impl LoxCallable for MapGet {
+    /// Value stored under the key, or nil if there's none
+    fn call(&self, args: Vec<Value>, _interpreter: &mut Interpreter) -> ValueResult {
+        let (map, key) = map_args(&args, "map_get")?;
+        Ok(map.borrow().get(&key).cloned().unwrap_or_default())
+    }
+    fn arity(&self) -> usize {
+        2
+    }
+}
+
+#[derive(Debug, Display)]
+#[display(fmt = "<native fn: map_set>")]
+pub struct MapSet;
+
+impl LoxCallable for MapSet {
+    /// Store a value under the key, replacing what was there. Returns the stored value
+    fn call(&self, args: Vec<Value>, _interpreter: &mut Interpreter) -> ValueResult {
+        let (map, key) = map_args(&args, "map_set")?;
+        map.borrow_mut().insert(key, args[2].clone());
+        Ok(args[2].clone())
+    }
+    fn arity(&self) -> usize {
+        3
+    }
+}
+
+#[derive(Debug, Display)]
+#[display(fmt = "<native fn: map_delete>")]
+pub struct MapDelete;
+
+impl LoxCallable for MapDelete {
+    /// Remove the key, returning the value it held or nil if it wasn't there
+    fn call(&self, args: Vec<Value>, _interpreter: &mut Interpreter) -> ValueResult {
+        let (map, key) = map_args(&args, "map_delete")?;
+        let removed = map.borrow_mut().remove(&key);
+        Ok(removed.unwrap_or_default())
+    }
+    fn arity(&self) -> usize {
+        2
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn roll_dice(interpreter: &mut Interpreter, seed: f64, times: usize) -> Vec<Value> {
+        Seed.call(vec![Value::Double(seed)], interpreter).unwrap();
+        (0..times)
+            .map(|_| {
+                RandomInt
+                    .call(vec![Value::Double(1.0), Value::Double(6.0)], interpreter)
+                    .unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn seeded_random_int_is_deterministic() {
+        let mut interpreter = Interpreter::default();
+        let first = roll_dice(&mut interpreter, 42.0, 20);
+        let second = roll_dice(&mut interpreter, 42.0, 20);
+        assert_eq!(first, second);
+        for roll in first {
+            let n = roll.is_numeric().unwrap();
+            assert!((1.0..=6.0).contains(&n) && n.fract() == 0.0, "{n} is not a dice roll");
+        }
+        assert_ne!(roll_dice(&mut interpreter, 7.0, 20), second);
+    }
+
+    #[test]
+    fn char_at_counts_chars() {
+        let mut interpreter = Interpreter::default();
+        let at = |i: f64, interpreter: &mut Interpreter| {
+            CharAt.call(vec![Value::String("héllo".into()), Value::Double(i)], interpreter)
+        };
+        assert_eq!(at(1.0, &mut interpreter), Ok(Value::Char('é')));
+        assert_eq!(at(4.0, &mut interpreter), Ok(Value::Char('o')));
+        assert!(matches!(at(5.0, &mut interpreter), Err(EvalError::InvalidArgument(_))));
+    }
+
+    #[test]
+    fn len_and_contains() {
+        let mut interpreter = Interpreter::default();
+        let array = |items: &[f64]| Value::from(items.iter().map(|&n| Value::Double(n)).collect::<Vec<_>>());
+        let hi = Value::String("hi".into());
+        assert_eq!(Len.call(vec![hi], &mut interpreter), Ok(Value::Double(2.0)));
+        assert_eq!(Len.call(vec![array(&[1.0, 2.0, 3.0])], &mut interpreter), Ok(Value::Double(3.0)));
+        let contains = |container: Value, item: Value, interpreter: &mut Interpreter| {
+            Contains.call(vec![container, item], interpreter)
+        };
+        assert_eq!(
+            contains("hello".to_string().into(), "ell".to_string().into(), &mut interpreter),
+            Ok(Value::Bool(true))
+        );
+        assert_eq!(
+            contains(array(&[1.0, 2.0]), Value::Double(3.0), &mut interpreter),
+            Ok(Value::Bool(false))
+        );
+        assert!(matches!(
+            Len.call(vec![Value::Double(1.0)], &mut interpreter),
+            Err(EvalError::InvalidArgument(_))
+        ));
+    }
+
+    #[test]
+    fn string_natives() {
+        let mut interpreter = Interpreter::default();
+        let s = |s: &str| Value::String(s.into());
+        assert_eq!(StartsWith.call(vec![s("hello"), s("he")], &mut interpreter), Ok(Value::Bool(true)));
+        assert_eq!(StartsWith.call(vec![s("hello"), s("lo")], &mut interpreter), Ok(Value::Bool(false)));
+        assert_eq!(EndsWith.call(vec![s("hello"), s("lo")], &mut interpreter), Ok(Value::Bool(true)));
+        assert_eq!(Trim.call(vec![s("  x  ")], &mut interpreter), Ok(s("x")));
+        assert!(matches!(
+            StartsWith.call(vec![s("hello"), Value::Double(1.0)], &mut interpreter),
+            Err(EvalError::InvalidArgument(_))
+        ));
+        assert!(matches!(Trim.call(vec![Value::Nil], &mut interpreter), Err(EvalError::InvalidArgument(_))));
+    }
+
+    #[test]
+    fn chars_splits_on_scalar_values() {
+        let mut interpreter = Interpreter::default();
+        let chars = |s: &str, interpreter: &mut Interpreter| match Chars.call(vec![Value::String(s.into())], interpreter) {
+            Ok(Value::Array(items)) => items.borrow().clone(),
+            other => panic!("expected an array, got {other:?}"),
+        };
+        assert_eq!(chars("abc", &mut interpreter), vec![Value::Char('a'), Value::Char('b'), Value::Char('c')]);
+        let accented = chars("héllo", &mut interpreter);
+        assert_eq!(accented.len(), 5);
+        assert_eq!(accented[1], Value::Char('é'));
+        assert!(chars("", &mut interpreter).is_empty());
+        assert!(matches!(Chars.call(vec![Value::Double(1.0)], &mut interpreter), Err(EvalError::InvalidArgument(_))));
+    }
+
+    #[test]
+    fn assertions() {
+        let mut interpreter = Interpreter::default();
+        let two = Value::Double(2.0);
+        assert_eq!(AssertEq.call(vec![(1.0 + 1.0).into(), two.clone()], &mut interpreter), Ok(Value::Nil));
+        match AssertEq.call(vec![Value::Double(1.0), two], &mut interpreter) {
+            Err(EvalError::AssertionFailed(msg)) => assert!(msg.contains('1') && msg.contains('2'), "{msg}"),
+            other => panic!("expected an assertion failure, got {other:?}"),
+        }
+        assert_eq!(AssertTrue.call(vec![Value::Bool(true)], &mut interpreter), Ok(Value::Nil));
+        assert!(matches!(AssertTrue.call(vec![Value::Nil], &mut interpreter), Err(EvalError::AssertionFailed(_))));
+    }
+
+    #[test]
+    fn map_natives() {
+        let mut interpreter = Interpreter::default();
+        let key = Value::String("answer".into());
+        let map = NewMap.call(vec![], &mut interpreter).unwrap();
+        assert_eq!(HasKey.call(vec![map.clone(), key.clone()], &mut interpreter), Ok(Value::Bool(false)));
+        assert_eq!(MapGet.call(vec![map.clone(), key.clone()], &mut interpreter), Ok(Value::Nil));
+        MapSet.call(vec![map.clone(), key.clone(), Value::Double(42.0)], &mut interpreter).unwrap();
+        assert_eq!(HasKey.call(vec![map.clone(), key.clone()], &mut interpreter), Ok(Value::Bool(true)));
+        assert_eq!(MapGet.call(vec![map.clone(), key.clone()], &mut interpreter), Ok(Value::Double(42.0)));
+        assert_eq!(map.to_string(), r#"{"answer": 42}"#);
+        assert_eq!(MapDelete.call(vec![map.clone(), key.clone()], &mut interpreter), Ok(Value::Double(42.0)));
+        assert_eq!(HasKey.call(vec![map.clone(), key.clone()], &mut interpreter), Ok(Value::Bool(false)));
+        assert_eq!(MapDelete.call(vec![map.clone(), key.clone()], &mut interpreter), Ok(Value::Nil));
+        assert!(matches!(
+            HasKey.call(vec![map, Value::Double(1.0)], &mut interpreter),
+            Err(EvalError::InvalidArgument(_))
+        ));
+        assert!(matches!(MapGet.call(vec![Value::Nil, key], &mut interpreter), Err(EvalError::InvalidArgument(_))));
+    }
+
+    #[test]
+    fn file_round_trip() {
+        let mut interpreter = Interpreter::default();
+        let path = std::env::temp_dir().join(format!("loxr-file-natives-{}.txt", std::process::id()));
+        let path = Value::String(path.to_string_lossy().as_ref().into());
+        let contents = Value::String("héllo\nlox".into());
+        assert_eq!(WriteFile.call(vec![path.clone(), contents.clone()], &mut interpreter), Ok(Value::Nil));
+        assert_eq!(ReadFile.call(vec![path.clone()], &mut interpreter), Ok(contents));
+        if let Value::String(path) = &path {
+            std::fs::remove_file(&**path).unwrap();
+        }
+        assert!(matches!(ReadFile.call(vec![path.clone()], &mut interpreter), Err(EvalError::IoError(_))));
+        assert!(matches!(
+            WriteFile.call(vec![path, Value::Double(1.0)], &mut interpreter),
+            Err(EvalError::InvalidArgument(_))
+        ));
+    }
+
+    /// Appends what happened to a shared log, as a writer and as a reader
+    #[derive(Clone)]
+    struct Logged(Rc<RefCell<Vec<&'static str>>>, &'static [u8]);
+
+    impl std::io::Write for Logged {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().push("write");
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            self.0.borrow_mut().push("flush");
+            Ok(())
+        }
+    }
+
+    impl std::io::Read for Logged {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.1.read(buf)
+        }
+    }
+
+    impl std::io::BufRead for Logged {
+        fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+            self.0.borrow_mut().push("read");
+            Ok(self.1)
+        }
+        fn consume(&mut self, amt: usize) {
+            self.1 = &self.1[amt..];
+        }
+    }
+
+    #[test]
+    fn read_line_flushes_output_first() {
+        let log = Rc::new(RefCell::new(vec![]));
+        let mut interpreter = Interpreter::default();
+        interpreter.set_output(Logged(Rc::clone(&log), b""));
+        interpreter.set_input(Logged(Rc::clone(&log), b"Ada\r\nlast"));
+        interpreter.output.write_line("name?");
+        assert_eq!(ReadLine.call(vec![], &mut interpreter), Ok(Value::String("Ada".into())));
+        let first = |event| log.borrow().iter().position(|e| *e == event).unwrap();
+        assert!(first("write") < first("flush") && first("flush") < first("read"), "{:?}", log.borrow());
+        assert_eq!(ReadLine.call(vec![], &mut interpreter), Ok(Value::String("last".into())));
+        assert_eq!(ReadLine.call(vec![], &mut interpreter), Ok(Value::Nil));
+    }
+
+    #[test]
+    fn abs_sign_and_clamp() {
+        let mut interpreter = Interpreter::default();
+        let n = Value::Double;
+        assert_eq!(Abs.call(vec![n(-2.5)], &mut interpreter), Ok(n(2.5)));
+        assert_eq!(Sign.call(vec![n(-3.0)], &mut interpreter), Ok(n(-1.0)));
+        assert_eq!(Sign.call(vec![n(0.0)], &mut interpreter), Ok(n(0.0)));
+        assert_eq!(Sign.call(vec![n(0.5)], &mut interpreter), Ok(n(1.0)));
+        assert_eq!(Clamp.call(vec![n(5.0), n(0.0), n(3.0)], &mut interpreter), Ok(n(3.0)));
+        assert_eq!(Clamp.call(vec![n(-1.0), n(0.0), n(3.0)], &mut interpreter), Ok(n(0.0)));
+        assert_eq!(Clamp.call(vec![n(1.5), n(0.0), n(3.0)], &mut interpreter), Ok(n(1.5)));
+        assert!(matches!(
+            Clamp.call(vec![n(1.0), n(3.0), n(0.0)], &mut interpreter),
+            Err(EvalError::InvalidArgument(_))
+        ));
+        assert!(matches!(
+            Sign.call(vec![Value::String("1".into())], &mut interpreter),
+            Err(EvalError::InvalidArgument(_))
+        ));
+    }
+
+    #[test]
+    fn version_is_not_empty() {
+        let version = Version.call(vec![], &mut Interpreter::default()).unwrap();
+        assert!(matches!(version, Value::String(v) if !v.is_empty()));
+    }
+
+    #[test]
+    fn hex_round_trip() {
+        let mut interpreter = Interpreter::default();
+        let decode = |hex: &str, interpreter: &mut Interpreter| HexDecode.call(vec![Value::String(hex.into())], interpreter);
+        let bytes = decode("48", &mut interpreter).unwrap();
+        assert_eq!(bytes, Value::Bytes(vec![0x48]));
+        assert_eq!(Len.call(vec![bytes.clone()], &mut interpreter), Ok(Value::Double(1.0)));
+        assert_eq!(bytes.to_string(), "<bytes len=1>");
+        let bytes = decode("00fF10", &mut interpreter).unwrap();
+        assert_eq!(HexEncode.call(vec![bytes], &mut interpreter), Ok(Value::String("00ff10".into())));
+        for bad in ["4", "zz", "é1", "+f", "-1"] {
+            assert!(matches!(decode(bad, &mut interpreter), Err(EvalError::InvalidArgument(_))), "{bad}");
+        }
+        assert!(HexEncode.call(vec![Value::String("48".into())], &mut interpreter).is_err());
+    }
+
+    #[test]
+    fn div_floors() {
+        let mut interpreter = Interpreter::default();
+        let mut div = |a: f64, b: f64| Div.call(vec![Value::Double(a), Value::Double(b)], &mut interpreter);
+        assert_eq!(div(7.0, 2.0), Ok(Value::Double(3.0)));
+        assert_eq!(div(-7.0, 2.0), Ok(Value::Double(-4.0)));
+        assert!(matches!(div(1.0, 0.0), Err(EvalError::InvalidArgument(_))));
+    }
+
+    #[test]
+    fn int_truncates_and_parses() {
+        let mut interpreter = Interpreter::default();
+        let mut int = |value: Value| Int.call(vec![value], &mut interpreter);
+        assert_eq!(int(Value::Double(3.9)), Ok(Value::Double(3.0)));
+        assert_eq!(int(Value::Double(-3.9)), Ok(Value::Double(-3.0)));
+        assert_eq!(int(Value::String("42".into())), Ok(Value::Double(42.0)));
+        assert!(matches!(int(Value::String("x".into())), Err(EvalError::InvalidArgument(_))));
+        assert!(matches!(int(Value::Nil), Err(EvalError::InvalidArgument(_))));
+    }
+
+    #[test]
+    fn approx_eq_uses_tolerance() {
+        let mut interpreter = Interpreter::default();
+        let mut approx_eq = |a: f64, b: f64, epsilon: f64| {
+            ApproxEq.call(vec![Value::Double(a), Value::Double(b), Value::Double(epsilon)], &mut interpreter)
+        };
+        assert_eq!(approx_eq(0.1 + 0.2, 0.3, 0.0001), Ok(Value::Bool(true)));
+        assert_eq!(approx_eq(1.0, 1.1, 0.0001), Ok(Value::Bool(false)));
+        assert!(approx_eq(1.0, 1.0, -1.0).is_err());
+        // `==` stays exact
+        assert_ne!(Value::Double(0.1 + 0.2), Value::Double(0.3));
+    }
+
+    #[test]
+    fn get_or_reads_globals() {
+        let mut interpreter = Interpreter::default();
+        interpreter.globals.define("port", Value::Double(8080.0));
+        let mut get_or = |name: &str| {
+            GetOr.call(vec![Value::String(name.into()), Value::Double(80.0)], &mut interpreter)
+        };
+        assert_eq!(get_or("port"), Ok(Value::Double(8080.0)));
+        assert_eq!(get_or("missing"), Ok(Value::Double(80.0)));
+    }
+
+    #[test]
+    fn globals_lists_user_globals() {
+        let mut interpreter = Interpreter::default();
+        assert_eq!(Globals.call(vec![], &mut interpreter), Ok(Value::from(vec![])));
+        interpreter.globals.define("b", Value::Double(2.0));
+        interpreter.globals.define("a", Value::String("one".into()));
+        assert_eq!(
+            Globals.call(vec![], &mut interpreter),
+            Ok(Value::from(vec![Value::String("a".into()), Value::String("b".into())]))
+        );
+    }
+
+    #[test]
+    fn exit_calls_hook() {
+        use std::{cell::Cell, rc::Rc};
+        let mut interpreter = Interpreter::default();
+        let status = Rc::new(Cell::new(None));
+        let recorded = Rc::clone(&status);
+        interpreter.set_exit_hook(move |code| recorded.set(Some(code)));
+        Exit.call(vec![Value::Double(3.0)], &mut interpreter).unwrap();
+        assert_eq!(status.get(), Some(3));
+        assert!(Exit.call(vec![Value::Double(1.5)], &mut interpreter).is_err());
+    }
+
+    /// Stand-in for a Lox comparator `fun (a, b) { return b - a; }`
+    #[derive(Debug, Display)]
+    #[display(fmt = "<native fn: descending>")]
+    struct Descending;
+    impl LoxCallable for Descending {
+        fn call(&self, args: Vec<Value>, _interpreter: &mut Interpreter) -> ValueResult {
+            Ok(Value::Double(args[1].is_numeric().unwrap() - args[0].is_numeric().unwrap()))
+        }
+        fn arity(&self) -> usize {
+            2
+        }
+    }
+
+    #[test]
+    fn sort_arrays() {
+        let mut interpreter = Interpreter::default();
+        let numbers = |items: &[f64]| Value::from(items.iter().map(|&n| Value::Double(n)).collect::<Vec<_>>());
+        let array = numbers(&[3.0, 1.0, 2.0]);
+        assert_eq!(Sort.call(vec![array.clone()], &mut interpreter), Ok(Value::Nil));
+        assert_eq!(array, numbers(&[1.0, 2.0, 3.0]));
+
+        let array = numbers(&[1.0, 3.0, 2.0]);
+        let cmp = Value::Function(std::rc::Rc::new(Descending));
+        Sort.call(vec![array.clone(), cmp], &mut interpreter).unwrap();
+        assert_eq!(array, numbers(&[3.0, 2.0, 1.0]));
+
+        let mixed = Value::from(vec![Value::Double(1.0), Value::String("a".into())]);
+        assert!(matches!(
+            Sort.call(vec![mixed], &mut interpreter),
+            Err(EvalError::InvalidArgument(_))
+        ));
+    }
+
+    /// Stand-in for a Lox function `fun (a, b) { return a + b; }`
+    #[derive(Debug, Display)]
+    #[display(fmt = "<native fn: add>")]
+    struct Add;
+    impl LoxCallable for Add {
+        fn call(&self, args: Vec<Value>, _interpreter: &mut Interpreter) -> ValueResult {
+            match (args[0].is_numeric(), args[1].is_numeric()) {
+                (Some(a), Some(b)) => Ok(Value::Double(a + b)),
+                _ => Err(EvalError::InvalidArgument("can only add numbers".into())),
+            }
+        }
+        fn arity(&self) -> usize {
+            2
+        }
+    }
+
+    #[test]
+    fn reduce_folds_left() {
+        let mut interpreter = Interpreter::default();
+        let add = Value::Function(std::rc::Rc::new(Add));
+        let numbers = Value::from((1..=4).map(|n| Value::Double(n as f64)).collect::<Vec<_>>());
+        let mut reduce = |array: Value, f: Value, initial: Value| Reduce.call(vec![array, f, initial], &mut interpreter);
+        assert_eq!(reduce(numbers.clone(), add.clone(), Value::Double(0.0)), Ok(Value::Double(10.0)));
+        assert_eq!(reduce(Value::from(vec![]), add.clone(), Value::Nil), Ok(Value::Nil));
+        // errors from the function stop the fold
+        assert!(matches!(
+            reduce(numbers.clone(), add, Value::String("a".into())),
+            Err(EvalError::InvalidArgument(_))
+        ));
+        assert!(matches!(
+            reduce(numbers, Value::Double(1.0), Value::Double(0.0)),
+            Err(EvalError::InvalidArgument(_))
+        ));
+    }
+
+    #[test]
+    fn random_is_in_unit_interval() {
+        let mut interpreter = Interpreter::default();
+        for _ in 0..100 {
+            let n = Random.call(vec![], &mut interpreter).unwrap().is_numeric().unwrap();
+            assert!((0.0..1.0).contains(&n));
+        }
+    }
+
+    #[test]
+    fn random_int_rejects_bad_bounds() {
+        let mut interpreter = Interpreter::default();
+        let reversed = RandomInt.call(vec![Value::Double(6.0), Value::Double(1.0)], &mut interpreter);
+        assert!(matches!(reversed, Err(EvalError::InvalidArgument(_))));
+        let fractional = RandomInt.call(vec![Value::Double(1.5), Value::Double(6.0)], &mut interpreter);
+        assert!(matches!(fractional, Err(EvalError::InvalidArgument(_))));
+        let wide = RandomInt.call(vec![Value::Double(-1e19), Value::Double(1e19)], &mut interpreter);
+        assert!(matches!(wide, Err(EvalError::InvalidArgument(_))));
+        let widest = RandomInt.call(vec![Value::Double(0.0), Value::Double(u64::MAX as f64)], &mut interpreter);
+        assert!(matches!(widest, Err(EvalError::InvalidArgument(_))));
+        let wide_but_fits = RandomInt.call(vec![Value::Double(-1e18), Value::Double(1e18)], &mut interpreter);
+        assert!(matches!(wide_but_fits, Ok(Value::Double(n)) if (-1e18..=1e18).contains(&n)));
+    }
+}
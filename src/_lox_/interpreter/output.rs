@@ -0,0 +1,94 @@
+use std::cell::RefCell;
+use std::io::{BufRead, Write};
+use std::rc::Rc;
+
+/// Destination for everything a Lox program prints. Defaults to stdout but can be
+/// swapped for any writer, e.g. a [SharedBuffer] to capture output in tests
+pub struct OutputSink(Box<dyn Write>);
+
+impl OutputSink {
+    pub fn new(writer: impl Write + 'static) -> Self {
+        Self(Box::new(writer))
+    }
+    pub fn stdout() -> Self {
+        Self::new(std::io::stdout())
+    }
+    pub fn stderr() -> Self {
+        Self::new(std::io::stderr())
+    }
+    /// Write `line` followed by a newline
+    pub fn write_line(&mut self, line: &str) {
+        writeln!(self.0, "{line}").expect("ICE: failed to write to output sink");
+    }
+    pub fn flush(&mut self) {
+        self.0.flush().expect("ICE: failed to flush output sink");
+    }
+}
+
+impl Default for OutputSink {
+    fn default() -> Self {
+        Self::stdout()
+    }
+}
+
+impl std::fmt::Debug for OutputSink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "OutputSink")
+    }
+}
+
+/// Where the `read_line` native reads from. Defaults to stdin but can be swapped for any
+/// buffered reader, e.g. a byte slice in tests
+pub struct InputSource(Box<dyn BufRead>);
+
+impl InputSource {
+    pub fn new(reader: impl BufRead + 'static) -> Self {
+        Self(Box::new(reader))
+    }
+    pub fn stdin() -> Self {
+        Self::new(std::io::BufReader::new(std::io::stdin()))
+    }
+    /// The next line without its line ending, `None` once the input is exhausted
+    pub fn read_line(&mut self) -> std::io::Result<Option<String>> {
+        let mut line = String::new();
+        if self.0.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let len = line.trim_end_matches(['\n', '\r']).len();
+        line.truncate(len);
+        Ok(Some(line))
+    }
+}
+
+impl Default for InputSource {
+    fn default() -> Self {
+        Self::stdin()
+    }
+}
+
+impl std::fmt::Debug for InputSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "InputSource")
+    }
+}
+
+/// A cloneable in-memory writer, every clone appends to the same buffer
+#[derive(Debug, Clone, Default)]
+pub struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+impl SharedBuffer {
+    /// Everything written so far, lossily decoded as UTF-8
+    pub fn contents(&self) -> String {
+        String::from_utf8_lossy(&self.0.borrow()).into_owned()
+    }
+}
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
@@ -56,7 +56,7 @@
 //!
 //! *primary*     → `literal | identifier | "(" expression ")";`
 //!
-//! *literal*        → `NUMBER | STRING | "true" | "false" | "nil" ;`
+//! *literal*        → `NUMBER | STRING | CHAR | "true" | "false" | "nil" ;`
 //!
 //! *grouping*       → `"(" expression ")" ;`
 //!
@@ -81,22 +81,33 @@ pub mod interpreter;
 /// ## Macros
 pub mod macros;
 
+/// ## Errors returned by [Lox::run]
+pub mod error;
+
 // use std::rc::Rc;
 
+use crate::error::{Diagnostic, LoxError};
 use crate::parser::Parser;
 use crate::tokenizer::scanner::Scanner;
 use colored::Colorize;
-use interpreter::Interpreter;
+use interpreter::{Interpreter, OutputSink, SharedBuffer};
+use parser::{error::{EvalError, ParserError}, statement::Stmt, value::Value};
 use tokenizer::token::Token;
 #[derive(Debug)]
 pub struct Lox {
     /// Error encountered?
     pub had_error: bool,
     pub had_runtime_error: bool,
+    /// Errors found by the scanner since the last run
+    pub diagnostics: Vec<Diagnostic>,
     /// Source string
     pub src: String,
     /// Repl interpreter
     pub repl_interpreter: Interpreter,
+    /// Let a line break end a statement in place of `;`, see [Parser::newline_terminated]
+    pub newline_terminators: bool,
+    /// Scan, parse and runtime errors of the last file run, see [Lox::error_summary]
+    pub error_count: usize,
 }
 
 impl Lox {
@@ -106,7 +117,10 @@ impl Lox {
             repl_interpreter: Interpreter::default(),
             had_error: false,
             had_runtime_error: false,
+            diagnostics: vec![],
             src,
+            newline_terminators: false,
+            error_count: 0,
         }
     }
     pub fn print_all_tokens(tokens: Vec<Token>) {
@@ -125,6 +139,25 @@ impl Lox {
             col_no = format!("column {col}").yellow()
         );
     }
+    /// Report a parse error that has no position to show
+    pub fn report_parse_err(err: &ParserError) {
+        eprintln!("{}: {err}", "Parse Error".red());
+    }
+    /// Report a parser warning, the program still runs
+    pub fn report_warning(warning: &Diagnostic) {
+        eprintln!(
+            "{warning_label}: {message} at {line_no}, {col_no}",
+            warning_label = "Warning".yellow(),
+            message = warning.message,
+            line_no = format!("line {}", warning.line).yellow(),
+            col_no = format!("column {}", warning.col).yellow()
+        );
+    }
+    /// Record a scanner error on `line`, the caller decides how to report it once scanning is done
+    pub fn scan_error(&mut self, line: usize, col: usize, message: String) {
+        self.had_error = true;
+        self.diagnostics.push(Diagnostic { line, col, message });
+    }
     /// Handler for errors that are thrown by the interpreter
     pub fn report_runtime_err(message: String) {
         eprintln!(
@@ -137,29 +170,93 @@ impl Lox {
         //     self.had_runtime_error = true;
         // }
     }
-    pub fn run(&mut self, line: Option<String>) {
+    /// Run `line` on the REPL interpreter, or the whole source if `line` is None.
+    /// Scan and parse errors stop the program before it runs, see [LoxError]
+    pub fn run(&mut self, line: Option<String>) -> Result<(), LoxError> {
         if let Some(src) = line {
             // Interpret
-            self.run_line(src);
+            self.run_line(src)
         } else {
             // Run file
             let src = self.src.clone();
-            let mut scanner = Scanner::new(&src, self);
-            scanner.scan_tokens();
-            let tokens = scanner.tokens;
-            let parser = Parser::new(tokens);
-            let mut interpreter = Interpreter::new(parser);
-            interpreter.interpret();
+            let stmts = self.parse(&src, false).inspect_err(|err| {
+                self.error_count = err.error_count();
+            })?;
+            let mut interpreter = Interpreter::with_stmts(stmts);
+            let result = interpreter.interpret();
+            self.error_count = interpreter.errors_reported();
+            result.map_err(|e| self.runtime_error(e))
+        }
+    }
+    /// "N errors" for the last file run, `None` if it had no errors
+    pub fn error_summary(&self) -> Option<String> {
+        match self.error_count {
+            0 => None,
+            1 => Some("1 error".into()),
+            n => Some(format!("{n} errors")),
         }
     }
     /// A REPL function. Interpret `src` as `lox` source and run it
-    pub fn run_line(&mut self, src: String) {
-        let mut scanner = Scanner::new(&src, self);
+    /// A line that ends before a statement is complete is rejected with [LoxError::Incomplete]
+    pub fn run_line(&mut self, src: String) -> Result<(), LoxError> {
+        let stmts = self.parse(&src, true)?;
+        self.repl_interpreter.repl = true;
+        self.repl_interpreter.extend(stmts).map_err(|e| self.runtime_error(e))
+    }
+    /// Run `src` on the REPL interpreter with its output captured, for embedding the interpreter.
+    /// Returns the value of a trailing expression, which may leave out its `;`, or of the last statement,
+    /// `None` if `src` failed to parse or run, along with everything the program printed. The output is
+    /// raw, printed values aren't prefixed with `>> ` and declarations aren't announced
+    pub fn eval_capture(&mut self, src: &str) -> (Option<Value>, String) {
+        let stmts = match self.parse(src, true) {
+            Err(LoxError::Incomplete(_)) => self.parse(&format!("{src};"), false),
+            parsed => parsed,
+        };
+        let captured = SharedBuffer::default();
+        let output = self.repl_interpreter.replace_output(OutputSink::new(captured.clone()));
+        self.repl_interpreter.raw_output = true;
+        let value = stmts.ok().and_then(|stmts| {
+            self.repl_interpreter.repl = true;
+            self.repl_interpreter.eval_stmts(&stmts).ok()
+        });
+        self.repl_interpreter.raw_output = false;
+        self.repl_interpreter.replace_output(output);
+        (value, captured.contents())
+    }
+    /// Scan and parse `src` into statements, `allow_incomplete` reports running out of tokens as [LoxError::Incomplete]
+    fn parse(&mut self, src: &str, allow_incomplete: bool) -> Result<Vec<Stmt>, LoxError> {
+        self.had_error = false;
+        self.diagnostics.clear();
+        let mut scanner = Scanner::new(src, self);
         scanner.scan_tokens();
         let tokens = scanner.tokens;
-        let parser = Parser::new(tokens);
-        self.repl_interpreter.repl = true;
-        self.repl_interpreter.extend(parser);
-        return;
+        if self.had_error {
+            return Err(LoxError::Scan(std::mem::take(&mut self.diagnostics)));
+        }
+        let mut parser = if self.newline_terminators {
+            Parser::newline_terminated(tokens)
+        } else {
+            Parser::new(tokens)
+        };
+        let stmts = parser.parse();
+        parser.take_warnings().iter().for_each(Lox::report_warning);
+        let incomplete = parser.incomplete().filter(|_| allow_incomplete);
+        if incomplete.is_none() {
+            // The rest were printed with their position while parsing
+            parser.unreported_errors().for_each(Lox::report_parse_err);
+        }
+        let errors = parser.take_errors();
+        if !errors.is_empty() {
+            if let Some(expected) = incomplete {
+                return Err(LoxError::Incomplete(expected));
+            }
+            self.had_error = true;
+            return Err(LoxError::Parse(errors));
+        }
+        Ok(stmts)
+    }
+    fn runtime_error(&mut self, err: EvalError) -> LoxError {
+        self.had_runtime_error = true;
+        LoxError::Runtime(err)
     }
 }
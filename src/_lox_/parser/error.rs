@@ -1,11 +1,12 @@
 use crate::parser::expressions::Expression;
+use crate::parser::value::Value;
 use crate::tokenizer::token::Token;
 use crate::tokenizer::token_type::TokenType;
 use thiserror::Error;
 use colored::Colorize;
 
 #[allow(unused)]
-#[derive(Error, Debug, PartialEq)]
+#[derive(Error, Debug, Clone, PartialEq)]
 pub enum ParserError {
     #[error("Parenthesis mismatch")]
     UnbalancedParen,
@@ -39,6 +40,12 @@ pub enum ParserError {
     InvalidFuncDecl,
     #[error("Invalid function arguments")]
     InvalidFuncArgs,
+    /// A parameter without a default after one with a default, holds the parameter name
+    #[error("Parameter '{0}' needs a default value, it follows a parameter that has one")]
+    MissingDefault(String),
+    /// A keyword where something else was expected, holds the keyword and what was expected
+    #[error("Cannot use keyword '{0}' as {1}")]
+    KeywordMisuse(String, &'static str),
 }
 
 
@@ -55,20 +62,90 @@ pub enum EvalError {
     InvalidExpr(Expression, Option<String>),
     #[error("Cannot evaluate Error production")]
     ErrorProduction,
+    /// A statement that failed to parse, carrying the parser's message
+    #[error("{}{0}", "Bad statement ".yellow())]
+    BadStatement(String),
     #[error("Cannot divide by zero in: {0}")]
     DivideByZero(Expression),
+    /// An error raised inside a parenthesized expression, with the group's source and
+    /// the line and column of its `(`
+    #[error("{0}, inside {1} at line {2}, column {3}")]
+    InGrouping(Box<EvalError>, String, usize, usize),
+    /// An error raised by the condition of a ternary, with the line and column of its `?`
+    #[error("{0}, in the condition of the ternary at line {1}, column {2}")]
+    TernaryCondition(Box<EvalError>, usize, usize),
     #[error("{0}")]
     VariableEval(RuntimeError),
     #[error("Break cannot be used outside loops")]
     BreakWithout,
+    #[error("Return cannot be used outside functions")]
+    ReturnOutsideFunction,
     #[error("{0}")]
     FunctionUndefined(RuntimeError),
-    #[error("Error parsing one of function arguments")]
-    FunctionArgError,
+    /// Callee, 1-based index of the first argument that failed to evaluate, line of the call and
+    /// the error the argument raised
+    #[error("Error evaluating argument {1} of call to {0} at line {2}: {3}")]
+    FunctionArgError(String, usize, usize, Box<EvalError>),
     #[error("Error calling function at {}", _0)]
     FunctionCallError(String),
     #[error("Expected {} but found {} arguments", _0, _1)]
-    ArityMismatch(usize, usize)
+    ArityMismatch(usize, usize),
+    #[error("Invalid argument: {0}")]
+    InvalidArgument(String),
+    /// Raised by the `error` native, catchable with `try`/`catch` like any other runtime error
+    #[error("{0}")]
+    Raised(String),
+    /// Raised by the `assert_eq` and `assert_true` natives
+    #[error("Assertion failed: {0}")]
+    AssertionFailed(String),
+    /// A file native failed to read or write, the message names the path and why
+    #[error("I/O error: {0}")]
+    IoError(String),
+    #[error("Only instances have properties, found a {0}")]
+    NoProperties(&'static str),
+    #[error("Cannot index into a {0}")]
+    NotIndexable(&'static str),
+    #[error("Cannot iterate over a {0}")]
+    NotIterable(&'static str),
+    #[error("Index {0} out of bounds for length {1}")]
+    IndexOutOfBounds(f64, usize),
+    #[error("Loop aborted after {0} iterations")]
+    IterationLimitExceeded(usize),
+    #[error("Scopes nested more than {0} deep")]
+    ScopeTooDeep(usize),
+    #[error("{0} cannot be used as a map key")]
+    UnhashableKey(&'static str),
+    #[error("repeat count must be a non-negative whole number, found {0}")]
+    InvalidRepeatCount(String),
+    #[error("Cannot destructure {1} values into {0} names")]
+    DestructureMismatch(usize, usize),
+    #[error("Cannot destructure a {0}, expected an array")]
+    DestructureNonArray(&'static str),
+    /// A `return` inside an expression, such as a `while` expression, on its way out of the expression.
+    /// [Interpreter::execute](crate::interpreter::Interpreter::execute) turns it back into a [Value::Return]
+    #[error("Return from inside an expression")]
+    ReturnFromExpression(Box<Value>),
+}
+
+impl EvalError {
+    /// The error at the bottom of the context added by groupings, ternary conditions and call arguments
+    pub fn innermost(&self) -> &EvalError {
+        match self {
+            EvalError::InGrouping(err, ..)
+            | EvalError::TernaryCondition(err, ..)
+            | EvalError::FunctionArgError(.., err) => err.innermost(),
+            err => err,
+        }
+    }
+    /// Like [innermost](EvalError::innermost) but by value
+    pub fn into_innermost(self) -> EvalError {
+        match self {
+            EvalError::InGrouping(err, ..)
+            | EvalError::TernaryCondition(err, ..)
+            | EvalError::FunctionArgError(.., err) => err.into_innermost(),
+            err => err,
+        }
+    }
 }
 
 #[derive(Error, Debug, PartialEq)]
@@ -79,4 +156,6 @@ pub enum RuntimeError {
     UndefinedVar(String),
     #[error("Function '{}' not declared before use ", _0.bright_yellow().bold())]
     UndefinedFunc(String),
+    #[error("Cannot assign to constant '{}'", _0.bright_yellow().bold())]
+    AssignToConst(String),
 }
\ No newline at end of file
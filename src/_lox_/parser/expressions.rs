@@ -1,6 +1,7 @@
 use derive_more::Display;
 use crate::tokenizer::token::Token;
 use crate::tokenizer::token_type::TokenType;
+use super::statement::Stmt;
 
 /// # The overarching Expression type
 ///
@@ -25,42 +26,62 @@ pub enum Expression {
     LogicOr(OrExpr),
     LogicAnd(AndExpr),
     Call(FnCallExpr),
+    /// Array literal `[a, b, c]`
+    Array(Vec<Box<Expression>>),
+    /// Property access `object.name`
+    Get(GetExpr),
+    /// Nil-safe property access `object?.name`, nil when `object` is nil
+    OptionalGet(GetExpr),
+    /// Indexing `object[index]`
+    Index(IndexExpr),
+    /// A `while` loop in expression position, `var x = while (c) { break 1; };`
+    While(WhileExpr),
 }
 
+/// Expressions display as the Lox source they were parsed from, modulo whitespace. Parentheses come from
+/// [Grouping]s, so printing an expression and parsing it again gives back the same tree
 impl std::fmt::Display for Expression {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        // Print display for variants which have the display method otherwise fallback to debug print
         let out = match &self {
             Expression::BinExpr(x) => format!("{x}"),
             Expression::UnExpr(x) => format!("{x}"),
             Expression::Lit(x) => format!("{x}"),
-            Expression::CommaExpr(x) => {
-                let mut res = String::new();
-                res.push_str("[\n");
-                for item in x {
-                    res.push_str(&format!("\t{item},\n"));
-                }
-                let mut res = res.trim_end_matches("\n").to_owned();
-                res.push_str("\n]");
-                res
+            Expression::CommaExpr(x) => format!(
+                "[{}]",
+                x.iter().map(|item| item.to_string()).collect::<Vec<_>>().join(", ")
+            ),
+            Expression::TernExpr(TernaryExpr { condition, if_true, if_false, .. }) => {
+                format!("{condition} ? {if_true} : {if_false}")
             }
-            Expression::TernExpr(x) => format!("{x:?}"),
-            Expression::Group(x) => format!("{x:?}"),
+            Expression::Group(x) => format!("({})", x.inner),
+            // Not Lox source, an error production only exists to be reported
             Expression::Error(x) => format!("{x:?}"),
             Expression::Assignment(AssignmentExpr { name, right }) => {
-                format!("{name} = {right}")
+                format!("{} = {right}", name.lexeme)
             }
-            Expression::Variable(t) => format!("{t}"),
+            Expression::Variable(t) => t.lexeme.clone(),
             Expression::LogicOr(l) => format!("{l}"),
             Expression::LogicAnd(l) => format!("{l}"),
             Expression::Call(e) => format!("{e}"),
+            Expression::Array(items) => format!(
+                "[{}]",
+                items.iter().map(|item| item.to_string()).collect::<Vec<_>>().join(", ")
+            ),
+            Expression::Get(e) => format!("{e}"),
+            Expression::OptionalGet(e) => format!("{}?.{}", e.object, e.name.lexeme),
+            Expression::Index(e) => format!("{e}"),
+            Expression::While(e) => format!("{e}"),
         };
         write!(f, "{out}")
     }
 }
 
 #[derive(Debug, PartialEq, Clone, Display)]
-#[display(fmt = "|Function Call to -> {:?} Args -> ({:?})|", callee, args)]
+#[display(
+    fmt = "{}({})",
+    callee,
+    r#"args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>().join(", ")"#
+)]
 pub struct FnCallExpr {
     /// Typically one would expect a function call to look like `some_var_name(..args?)`
     /// Where some_var_name is a Variable token. But since 
@@ -79,14 +100,39 @@ impl FnCallExpr {
 }
 
 #[derive(Debug, PartialEq, Clone, Display)]
-#[display(fmt = "LogicalAnd(Left [{}] and Right [{}])", left, right)]
+#[display(fmt = "{}.{}", object, "name.lexeme")]
+pub struct GetExpr {
+    pub object: Box<Expression>,
+    /// The property name, an IDENTIFIER token
+    pub name: Token,
+}
+
+/// Evaluates to the value of the `break` that ends the loop, nil when the condition ends it
+#[derive(Debug, PartialEq, Clone, Display)]
+#[display(fmt = "while ({}) {}", condition, body)]
+pub struct WhileExpr {
+    pub condition: Box<Expression>,
+    pub body: Box<Stmt>,
+}
+
+#[derive(Debug, PartialEq, Clone, Display)]
+#[display(fmt = "{}[{}]", object, index)]
+pub struct IndexExpr {
+    pub object: Box<Expression>,
+    pub index: Box<Expression>,
+    /// Stores the token ']' to report a runtime err incase of a bad index
+    pub bracket: Token,
+}
+
+#[derive(Debug, PartialEq, Clone, Display)]
+#[display(fmt = "{} and {}", left, right)]
 pub struct AndExpr {
     pub left: Box<Expression>,
     pub operator: Token, // type AND
     pub right: Box<Expression>,
 }
 #[derive(Debug, PartialEq, Clone, Display)]
-#[display(fmt = "LogicalOr(Left [{}] or Right [{}])", left, right)]
+#[display(fmt = "{} or {}", left, right)]
 pub struct OrExpr {
     pub left: Box<Expression>,
     pub operator: Token, // type OR
@@ -103,12 +149,14 @@ pub struct AssignmentExpr {
 #[derive(Debug, PartialEq, Clone)]
 pub struct TernaryExpr {
     pub condition: Box<Expression>,
+    /// The `?`, locates errors raised by the condition
+    pub question: Token,
     pub if_true: Box<Expression>,
     pub if_false: Box<Expression>,
 }
 
 #[derive(Debug, PartialEq, Clone, Display)]
-#[display(fmt = "{left} {operator} {right}")]
+#[display(fmt = "{} {} {}", left, "operator.lexeme", right)]
 pub struct BinaryExpr {
     pub left: Box<Expression>,
     pub operator: Token,
@@ -126,7 +174,7 @@ impl BinaryExpr {
 }
 
 #[derive(Debug, PartialEq, Clone, Display)]
-#[display(fmt = "{operator}{operand}")]
+#[display(fmt = "{}{}", "operator.lexeme", operand)]
 
 pub struct UnaryExpr {
     pub operator: Token,
@@ -137,7 +185,7 @@ impl UnaryExpr {
     /// Question: What happens if operand : is a UnaryExpr. Nothing special, valid syntax
     pub fn new(operator: Token, operand: Box<Expression>) -> Result<Self, String> {
         match operator.r#type {
-            TokenType::MINUS | TokenType::BANG => Ok(Self { operand, operator }),
+            TokenType::MINUS | TokenType::PLUS | TokenType::BANG => Ok(Self { operand, operator }),
             u => Err(format!(
                 "Cannot construct Unary expression with operator: {u:?}"
             )),
@@ -145,13 +193,49 @@ impl UnaryExpr {
     }
 }
 
-#[derive(Debug, PartialEq, Clone, Default, Display)]
-#[display(fmt = "{inner}")]
-
+#[derive(Debug, PartialEq, Clone, Default)]
 pub struct Literal {
     pub inner: Token,
 }
 
+impl std::fmt::Display for Literal {
+    /// The literal as written in source, string and char lexemes are stored without their quotes
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let lexeme = &self.inner.lexeme;
+        match self.inner.r#type {
+            // The scanner unescaped the string, escape it again so it reads back the same
+            TokenType::STRING => {
+                let mut escaped = String::with_capacity(lexeme.len());
+                for c in lexeme.chars() {
+                    match c {
+                        '\n' => escaped.push_str("\\n"),
+                        '\t' => escaped.push_str("\\t"),
+                        '\r' => escaped.push_str("\\r"),
+                        '\0' => escaped.push_str("\\0"),
+                        '\\' => escaped.push_str("\\\\"),
+                        '"' => escaped.push_str("\\\""),
+                        c => escaped.push(c),
+                    }
+                }
+                write!(f, "\"{escaped}\"")
+            }
+            TokenType::CHAR => {
+                let escaped = match lexeme.as_str() {
+                    "\n" => "\\n",
+                    "\t" => "\\t",
+                    "\r" => "\\r",
+                    "\0" => "\\0",
+                    "\\" => "\\\\",
+                    "'" => "\\'",
+                    other => other,
+                };
+                write!(f, "'{escaped}'")
+            }
+            _ => write!(f, "{lexeme}"),
+        }
+    }
+}
+
 impl Literal {
     pub fn new(inner: Token) -> Result<Self, String> {
         let token_type = inner.r#type;
@@ -168,11 +252,17 @@ impl Literal {
 #[derive(Debug, PartialEq, Clone)]
 pub struct Grouping {
     pub inner: Box<Expression>,
+    /// The opening `(`, locates errors raised inside the group. `None` for groups that weren't parsed
+    pub paren: Option<Token>,
 }
 
 impl Grouping {
     pub fn new(inner: Box<Expression>) -> Self {
-        Self { inner }
+        Self { inner, paren: None }
+    }
+    /// A group that was opened by `paren`
+    pub fn at(inner: Box<Expression>, paren: Token) -> Self {
+        Self { inner, paren: Some(paren) }
     }
 }
 
@@ -210,6 +300,7 @@ mod test {
                 right: Box::new(five),
                 operator: Token::new(TokenType::SLASH, "/".into(), line_number, col),
             })),
+            paren: None,
         });
 
         let group245 = Expression::Group(Grouping {
@@ -218,6 +309,7 @@ mod test {
                 right: Box::new(group45),
                 operator: Token::new(TokenType::MINUS, "-".into(), line_number, col),
             })),
+            paren: None,
         });
 
         let r#final = Expression::BinExpr(BinaryExpr {
@@ -228,4 +320,54 @@ mod test {
 
         println!("{:?}", r#final.print());
     }
+
+    #[test]
+    fn comma_expression_display() {
+        let number = |n: &str, col| {
+            Box::new(Expression::Lit(Literal::new(Token::new(TokenType::NUMBER, n.into(), 1, col)).unwrap()))
+        };
+        let comma = Expression::CommaExpr(vec![number("1", 1), number("2", 4), number("1", 7)]);
+        assert_eq!(comma.to_string(), "[1, 2, 1]");
+    }
+
+    /// Debug form of `expr` with token positions blanked out
+    fn shape(expr: &Expression) -> String {
+        let debug = format!("{expr:?}");
+        let mut out = String::new();
+        let mut rest = debug.as_str();
+        while let Some(at) = [rest.find("ln: "), rest.find("col: ")].into_iter().flatten().min() {
+            let label_end = at + rest[at..].find(' ').unwrap() + 1;
+            out.push_str(&rest[..label_end]);
+            rest = rest[label_end..].trim_start_matches(|c: char| c.is_ascii_digit());
+        }
+        out + rest
+    }
+
+    #[test]
+    fn display_round_trips() {
+        let parse = |src: &str| {
+            let mut lox = crate::Lox::new(src.into());
+            let mut scanner = crate::tokenizer::scanner::Scanner::new(src, &mut lox);
+            scanner.scan_tokens();
+            crate::parser::Parser::new(scanner.tokens).run().expect(src)
+        };
+        // spacing isn't kept, the tree is
+        let spaced = parse("(1+2)*  -3");
+        assert_eq!(spaced.to_string(), "(1 + 2) * -3");
+        assert_eq!(shape(&parse(&spaced.to_string())), shape(&spaced));
+        for src in [
+            "1 + 2 * 3",
+            "a ? b : c ? \"d\" : 'e'",
+            "!(a and b or c)",
+            "x = f(1, '\\n')(g)",
+            "[1, [2]][0][i - 1]",
+            "a?.b == nil",
+            r#""say \"hi\"\n\tto \\ and \r\0""#,
+        ] {
+            let expr = parse(src);
+            let printed = expr.to_string();
+            assert_eq!(printed, src);
+            assert_eq!(shape(&parse(&printed)), shape(&expr), "{src}");
+        }
+    }
 }
@@ -2,7 +2,9 @@
 //! program          → `statements`* EOF;
 //! 
 //! We may declare a variable or declare and assign the result of some expression to it
-//! variableDecl     → "var" IDENTIFIER ("=" expression)? ";" ;
+//! variableDecl     → "var" IDENTIFIER ("=" expression)? ";"
+//!                  | "var" "(" IDENTIFIER ( "," IDENTIFIER )* ")" "=" expression ";" ;
+//! constDecl        → "const" IDENTIFIER "=" expression ";" ;
 //! 
 //! funDecl          → "fun" function ; 
 //! function         → IDENTIFIER "(" params? ")" block;
@@ -10,11 +12,16 @@
 //! 
 //! statement        → `variableDecl`| `exprStmt` | `printStmt` | `block` | `ifstmt` ;
 //! exprStmt         → `expression` ";" ;
-//! printStmt        → print `expression` ";" ;
+//! printStmt        → print `expression`? ";" ;
 //! block            → "{" declaration* "}" ;
 //! ifStmt           → "if" "(" expression ")"  statement ("else" statement)? ;
 //! *whileStmt*      → `"while" "(" expression ")"  statement` ;
+//! *repeatStmt*     → `"repeat" "(" expression ")"  statement` ;
+//! *tryStmt*        → `"try" block "catch" "(" IDENTIFIER ")" block` ;
+//! *switchStmt*     → `"switch" "(" expression ")" "{" ( "case" expression ":" declaration* | "default" ":" declaration* )* "}"` ;
+//! *breakStmt*      → `"break" expression? ";"` ;
 //! *forStmt*        → `"for" "(" (varDecl | exprStmt) ";" expression? ";" expression? ";" ")"  ;
+//! *forInStmt*      → `"for" "(" ( IDENTIFIER "," )? IDENTIFIER "in" expression ")" statement` ;
 //!
 //! 
 //! A comma expression evaluates to the final expression
@@ -38,15 +45,17 @@
 //!
 //! *factor*      → `unary (( "%" | "/" | "*" ) unary )*;`
 //!
-//! *unary*       → `("-" | "!") unary | primary;`
+//! *unary*       → `("-" | "+" | "!") unary | call;`
 //!
-//! *primary*     → `literal | identifier | "(" expression ")";`
+//! *call*        → `primary ( "(" arguments? ")" | "[" expression "]" | ( "." | "?." ) IDENTIFIER )*;`
 //!
-//! *literal*        → `NUMBER | STRING | "true" | "false" | "nil" ;`
+//! *primary*     → `literal | identifier | "(" expression ")" | "[" arguments? "]" | whileStmt;`
+//!
+//! *literal*        → `NUMBER | STRING | CHAR | "true" | "false" | "nil" ;`
 //!
 //! *grouping*       → `"(" expression ")" ;`
 //!
-//! *unary*          → `( "-" | "!" ) expression ;`
+//! *unary*          → `( "-" | "+" | "!" ) expression ;`
 //!
 //! *binary*         → `expression operator expression ;`
 //!
@@ -77,6 +86,7 @@ use self::error::ParserError;
 use self::statement::Stmt;
 
 use crate::Lox;
+use crate::error::Diagnostic;
 /// ParserError
 pub mod error;
 
@@ -98,6 +108,19 @@ pub struct Parser {
     previous: Option<Token>,
     error_production : Vec<Token>,
     parser_corrupt: bool,
+    /// Every error that was turned into an `ErrStmt` while parsing statements
+    errors: Vec<ParserError>,
+    /// One per error in `errors`, whether it was printed with its position where it was found
+    reported: Vec<bool>,
+    /// Set once the statement being parsed has printed a syntax error
+    statement_reported: bool,
+    /// What the parser was looking for when it first ran out of tokens, `MISSING_OPERAND` if it was an expression
+    incomplete: Option<TokenType>,
+    /// Suspicious but legal code, reported without failing the parse
+    warnings: Vec<Diagnostic>,
+    /// Set while parsing the then-branch of an `if` outside of any block. An `else` there belongs to
+    /// the `if`, so a loop parsed in that position can't take an `else` of its own
+    in_then_branch: bool,
 }
 /// In a recursive descent parser, the least priority rule is matched first
 /// as we descend down into nested grammer rules
@@ -130,6 +153,7 @@ impl Parser {
         let conditional_expr = self.assignment()?;
         // loc!(format!("ternary here with condition/left -> {conditional_expr}"));
         if self.matches(&[TERNARYC]) {
+            let question = self.previous.take().expect("matched '?'");
             let left_expr = self.expression()?;
             // loc!(format!("ternary here with left -> {left_expr}"));
             if self.matches(&[TERNARYE]) {
@@ -137,6 +161,7 @@ impl Parser {
                 // loc!(format!("ternary here with right -> {right_expr}"));
                 let t = Expression::TernExpr(TernaryExpr {
                     condition: conditional_expr,
+                    question,
                     if_true: left_expr,
                     if_false: right_expr,
                 });
@@ -178,7 +203,7 @@ impl Parser {
                     })
                 )
             } else {
-                Lox::report_syntax_err(equal.ln, equal.col, format!("{}", ParserError::InvalidAssignmentTarget));
+                self.syntax_error(equal.ln, equal.col, format!("{}", ParserError::InvalidAssignmentTarget));
                 return Err(ParserError::InvalidAssignmentTarget);
             }
         }
@@ -285,12 +310,12 @@ impl Parser {
                 had_binary_expr_err = true;
                 // TODO: This code results in assymetric error reporting
                 // for example `var x = 10-*;` produces a different error message than `var x = 10*-`
-                report_token_error(&i);
+                self.report_token_error(&i);
                 loop {
                     let maybe_valid = self.primary();
                     if let Err(ParserError::InvalidToken(ref i2)) = maybe_valid  
                     {
-                        report_token_error(i2)
+                        self.report_token_error(i2)
                     }
                     if maybe_valid.is_ok() { break maybe_valid?; }
                     counter += 1;    
@@ -313,9 +338,10 @@ impl Parser {
         }
         Ok(expr)
     }
-    /// *unary*       → `("-" | "!") unary | call;`
+    /// *unary*       → `("-" | "+" | "!") unary | call;`
+    /// Unary `+` is a no-op on numbers, there for symmetry with `-`
     pub fn unary(&mut self) -> Result<Box<Expression>, ParserError> {
-        if self.matches(&[MINUS, BANG]) {
+        if self.matches(&[MINUS, PLUS, BANG]) {
             let operator: Token = self
             .previous
             .take()
@@ -328,9 +354,11 @@ impl Parser {
         }
         self.call()
     }
-    /// *call*        → `primary( "(" arguments? ")" )*` ;
+    /// *call*        → `primary ( "(" arguments? ")" | "[" expression "]" | ( "." | "?." ) IDENTIFIER )*` ;
     /// *arguments*   → expression ( "," expression )* ;
     /// The rule uses * to allow matching a series of calls like fn(1)(2)(3).
+    /// Calls, indexes and property accesses mix in any order, `a.b()[0].c` is
+    /// the property `c` of index `0` of the result of calling `a.b`
     pub fn call(&mut self) -> Result<Box<Expression>, ParserError>
     {
         let mut expr = self.primary()?;
@@ -339,6 +367,16 @@ impl Parser {
             {
                 // The returned expr becomes the new callee expression in case of fn(1)(2)
                 expr = self.finish_call(expr)?;
+            } else if self.matches(&[LEFT_SQUARE]) {
+                let index = self.parse_expression()?;
+                let bracket = self.consume(RIGHT_SQUARE)?.expect("consume returns the matched token");
+                expr = box Expression::Index(IndexExpr { object: expr, index, bracket });
+            } else if self.matches(&[DOT]) {
+                let name = self.consume(IDENTIFIER)?.expect("consume returns the matched token");
+                expr = box Expression::Get(GetExpr { object: expr, name });
+            } else if self.matches(&[QUESTION_DOT]) {
+                let name = self.consume(IDENTIFIER)?.expect("consume returns the matched token");
+                expr = box Expression::OptionalGet(GetExpr { object: expr, name });
             } else {
                 break 'nested_calls;
             }
@@ -360,7 +398,7 @@ impl Parser {
             {
                 if args.len() > 254 {
                     if let Some(next) = self.peek().cloned() {
-                    Lox::report_syntax_err(next.ln, next.col, format!("Too many arguments to function, consider removing arguments `{}` and others", next.to_string().bright_yellow()));
+                    self.syntax_error(next.ln, next.col, format!("Too many arguments to function, consider removing arguments `{}` and others", next.to_string().bright_yellow()));
                     // return Err(ParserError::TooManyArgs(self.peek().cloned()))
                     }
                 }
@@ -383,15 +421,16 @@ impl Parser {
             return Ok(box Expression::Variable(self.previous.take().expect("infallible")));
         }
         // "1+3+4(3+4)"
-        if self.matches(&[FALSE, TRUE, NIL, NUMBER, STRING]) {
+        if self.matches(&[FALSE, TRUE, NIL, NUMBER, STRING, CHAR]) {
             // Previous is sure to exist if this branch is entered
             // Also constructing a literal is infallible at this stage
             let _p = self.previous.clone().expect("Previous should have something here");
             let x = self.peek().cloned();
             if let Some(peeked_token) = x {
                 match peeked_token.r#type {
-                    LEFT_PAREN | LEFT_BRACE | LEFT_SQUARE => {
-                        Lox::report_syntax_err(
+                    // `[` is left out as indexing a literal is grammatical, `"abc"[0]`
+                    LEFT_PAREN | LEFT_BRACE => {
+                        self.syntax_error(
                             peeked_token.ln, 
                             peeked_token.col, 
                             format!("Unexpected token {peeked_token} after {_p}")
@@ -407,12 +446,25 @@ impl Parser {
                 Literal::new(self.previous.take().unwrap()).unwrap(),
             )))
         } else if self.matches(&[LEFT_PAREN]) {
+            let paren = self.previous.take();
             let expr = self.expression()?;
             let _expect_right_paren = self.consume(RIGHT_PAREN)?;
             // This assertion should never fail
             assert!(_expect_right_paren.is_some());
             // .expect("Expect ')' after expression");
-            Ok(Box::new(Expression::Group(Grouping::new(expr))))
+            Ok(Box::new(Expression::Group(match paren {
+                Some(paren) => Grouping::at(expr, paren),
+                None => Grouping::new(expr),
+            })))
+        } else if self.matches(&[LEFT_SQUARE]) {
+            self.array_literal()
+        } else if self.matches(&[WHILE]) {
+            // A loop in expression position evaluates to the value it breaks with
+            self.consume(LEFT_PAREN)?;
+            let condition = self.parse_expression()?;
+            self.consume(RIGHT_PAREN)?;
+            let body = box self.collect();
+            Ok(box Expression::While(WhileExpr { condition, body }))
         } else {
             // If there's going to be an illegal parse, it's going to be here
             self.parser_corrupt = true;
@@ -420,6 +472,9 @@ impl Parser {
             // fits into the grammar production after falling from expression upto token, in which case we have to write code
             // to handle that, or the Token is simply in the wrong place and a parser error should be reported "
             // panic!("Cannot parse as primary expression");
+            if let Some(token) = self.peek() && token.is_keyword() {
+                return Err(ParserError::KeywordMisuse(token.lexeme.clone(), "an expression"));
+            }
             if !self.is_at_end() && self.matches(&[PLUS, MINUS, SLASH, STAR, EQUAL_EQUAL, BANG_EQUAL, EQUAL, LESS, GREATER, LESS_EQUAL, GREATER_EQUAL]){
                 // Capture multiple invalid tokens or operators appearing at start of expression
                 self.error_production.push(self.previous.clone().expect("Matches will always be something"));
@@ -431,18 +486,32 @@ impl Parser {
             else {
                 // self.is_at_end == true and a primary expression is being searched for, but since is_at_end == true,
                 // the next token is EOF, and therefore the expression is ill-formed
+                if self.is_at_end() {
+                    self.incomplete.get_or_insert(MISSING_OPERAND);
+                }
                 Err(ParserError::ExpectedExpression)
             }
         }
     }
 }
 
-fn report_token_error(i: &Option<Token>) {
-    if let Some(invalid_token) = i {
-        let message = format!("Invalid token: '{}' ,found at what appears to be the boundary of a Binary Expression", invalid_token.lexeme);
-        Lox::report_syntax_err(invalid_token.ln, invalid_token.col, message);
+impl Parser {
+    /// Parse the elements of an array literal, the opening `[` has already been consumed
+    fn array_literal(&mut self) -> Result<Box<Expression>, ParserError> {
+        let mut items = vec![];
+        if !self.matches(&[RIGHT_SQUARE]) {
+            loop {
+                items.push(self.expression()?);
+                if !self.matches(&[COMMA]) {
+                    break;
+                }
+            }
+            self.consume(RIGHT_SQUARE)?;
+        }
+        Ok(box Expression::Array(items))
     }
 }
+
 // Private helpers
 impl Parser {
     /// Peeks the current token iterator for a match in the list of searchable token types passed to it.
@@ -481,6 +550,12 @@ impl Parser {
     fn peek(&mut self) -> Option<&Token> {
         self.tokens.peek()
     }
+    /// Types of the next `N` tokens. Must be called right after a token was consumed: `peek_n` only
+    /// fills its cache on the first call after an advance, so the furthest token is peeked first
+    fn peek_types<const N: usize>(&mut self) -> [Option<TokenType>; N] {
+        self.tokens.peek_n(N - 1);
+        std::array::from_fn(|n| self.tokens.peek_n(n).map(|t| t.r#type))
+    }
     /// Consume the token if & only if it matches the `expected_token` and return it, otherwise report an error,
     /// and return a `ParserError`. 
     fn consume(
@@ -491,8 +566,16 @@ impl Parser {
         if let Some(peeked_token) = self.tokens.peek() && expected_token == peeked_token.r#type {
             return Ok(self.advance());
         }
+        else if let Some(peeked_token) = self.tokens.peek() && expected_token == IDENTIFIER && peeked_token.is_keyword() {
+            let err = ParserError::KeywordMisuse(peeked_token.lexeme.clone(), "an identifier");
+            let (ln, col) = (peeked_token.ln, peeked_token.col);
+            self.syntax_error(ln, col, err.to_string());
+            Err(err)
+        }
         else if let Some(peeked_token) = self.tokens.peek() && peeked_token.r#type != EOF { 
-            Lox::report_syntax_err(peeked_token.ln, peeked_token.col, format!("Invalid Token: {peeked_token} encountered\nExpected {expected_token:#?}") );
+            let message = format!("Invalid Token: {peeked_token} encountered\nExpected {expected_token:#?}");
+            let (ln, col) = (peeked_token.ln, peeked_token.col);
+            self.syntax_error(ln, col, message);
             loc!();
             Err(ParserError::InvalidToken(self.tokens.peek().cloned()))
         } 
@@ -507,9 +590,10 @@ impl Parser {
             // let peeked_token = self.previous.clone().unwrap();
             // We should enter this condition
             if let Some(peeked_token) = self.tokens.peek() && peeked_token.r#type == EOF {
-                // This should report EOF in the error msg
+                // Not reported here, running out of tokens is either an incomplete REPL line
+                // or reported by the caller through the returned UnexpectedEOF
                 loc!();
-                Lox::report_syntax_err(peeked_token.ln, peeked_token.col, format!("Unexpected end of file, found {:#?}, expected `{expected_token:?}`", peeked_token.r#type));
+                self.incomplete.get_or_insert(expected_token);
                 return Err(ParserError::UnexpectedEOF);
             }
             loc!();
@@ -523,14 +607,14 @@ impl Parser {
     fn synchronize(&mut self) {
         self.advance();
         while !self.is_at_end() {
-            // After a semicolon, a Statement ends
+            // After a semicolon, a Statement ends. Comments need no handling here, `Parser::new` drops them
             if let Some(previous_token) = &self.previous && previous_token.r#type == SEMICOLON {
                 return;
             }
             if let Some(token) = self.peek() {
                 match token.r#type {
                     // Keywords that mark the beginning of a new Statement
-                   CLASS | FUN | VAR | FOR | IF | WHILE | PRINT | RETURN => 
+                   CLASS | FUN | VAR | CONST | FOR | IF | WHILE | REPEAT | TRY | SWITCH | PRINT | RETURN => 
                    {
                     return;
                    }
@@ -544,8 +628,8 @@ impl Parser {
 // Statement parsing
 impl Parser {
     pub fn new(mut tokens: Vec<Token>) -> Self {
-        // Filter out comment tokens
-        const COMMENTS : [TokenType;2] = [MULTI_LINE_COMMENT, COMMENT];
+        // Filter out comment tokens, and line breaks which only matter to `newline_terminated`
+        const COMMENTS : [TokenType;3] = [MULTI_LINE_COMMENT, COMMENT, NEWLINE];
         tokens = tokens.into_iter().filter(|t| !COMMENTS.contains(&t.r#type) ).collect();
         // println!("tokens filtered -> {:?}", tokens.clone().into_iter().map(|x| x.r#type).collect::<Vec<TokenType>>());
         Self {
@@ -554,16 +638,102 @@ impl Parser {
             previous: None,
             error_production: vec![],
             parser_corrupt: false,
+            errors: vec![],
+            reported: vec![],
+            statement_reported: false,
+            incomplete: None,
+            warnings: vec![],
+            in_then_branch: false,
+        }
+    }
+    /// A parser for tokens scanned with `NEWLINE` tokens, where a line break ends a statement like `;` does.
+    /// A `;` is put in place of a line break (or the end of input) that follows a token which can end a statement,
+    /// unless the next line starts with `{` or `else` so that a body can go on its own line
+    pub fn newline_terminated(tokens: Vec<Token>) -> Self {
+        let mut terminated: Vec<Token> = Vec::with_capacity(tokens.len());
+        let mut tokens = tokens.into_iter().peekable();
+        while let Some(token) = tokens.next() {
+            // A single line comment runs up to and including the line break, and the last line needs no break
+            let line_break = matches!(token.r#type, NEWLINE | COMMENT | EOF);
+            if !line_break {
+                terminated.push(token);
+                continue;
+            }
+            let ends_statement = terminated.iter().rev().find(|t| t.r#type != MULTI_LINE_COMMENT).is_some_and(|t| {
+                t.r#type.is_primary() || matches!(t.r#type, RIGHT_PAREN | RIGHT_SQUARE | BREAK | RETURN | PRINT)
+            });
+            let continues = tokens.peek().is_some_and(|next| matches!(next.r#type, LEFT_BRACE | ELSE));
+            if ends_statement && !continues {
+                terminated.push(Token::new(SEMICOLON, ";".into(), token.ln, token.col));
+            }
+            if token.r#type == EOF {
+                terminated.push(token);
+            }
         }
+        Self::new(terminated)
+    }
+    /// Errors collected by [Parser::parse], leaving the parser with none
+    pub fn take_errors(&mut self) -> Vec<ParserError> {
+        self.reported.clear();
+        std::mem::take(&mut self.errors)
+    }
+    /// The errors collected so far that weren't printed where they were found
+    pub fn unreported_errors(&self) -> impl Iterator<Item = &ParserError> {
+        self.errors.iter().zip(&self.reported).filter(|(_, reported)| !**reported).map(|(err, _)| err)
+    }
+    /// Warnings collected while parsing, leaving the parser with none
+    pub fn take_warnings(&mut self) -> Vec<Diagnostic> {
+        std::mem::take(&mut self.warnings)
+    }
+    /// If parsing failed because the tokens ran out, this is what was expected next
+    /// (`MISSING_OPERAND` for an expression). The REPL uses this to ask for more input
+    /// instead of rejecting a line like `1 +`
+    pub fn incomplete(&self) -> Option<TokenType> {
+        self.incomplete
     }
-    /// Parse as an expression
+    /// Remember `err` and turn it into an `ErrStmt` so that parsing can carry on
+    fn error_stmt(&mut self, err: ParserError) -> Stmt {
+        let stmt = Stmt::ErrStmt { message: format!("{err}") };
+        self.errors.push(err);
+        self.reported.push(std::mem::take(&mut self.statement_reported));
+        stmt
+    }
+    /// Print a syntax error where it was found, see [unreported_errors](Parser::unreported_errors)
+    fn syntax_error(&mut self, line: usize, col: usize, message: String) {
+        Lox::report_syntax_err(line, col, message);
+        self.statement_reported = true;
+    }
+    fn report_token_error(&mut self, i: &Option<Token>) {
+        if let Some(invalid_token) = i {
+            let message = format!("Invalid token: '{}' ,found at what appears to be the boundary of a Binary Expression", invalid_token.lexeme);
+            self.syntax_error(invalid_token.ln, invalid_token.col, message);
+        }
+    }
+    /// Parse as an expression. The whole input has to be a single expression, optionally followed by `;`,
+    /// and one that only parsed by recovering from an illegal token is an error rather than a tree
     pub fn run(&mut self) -> Result<Box<Expression>, ParserError> {
-        self.parse_expression()
+        let expr = self.parse_expression()?;
+        if std::mem::take(&mut self.parser_corrupt) {
+            return Err(ParserError::ErrorProduction(expr));
+        }
+        self.matches(&[SEMICOLON]);
+        if !self.is_at_end() {
+            return Err(ParserError::InvalidToken(self.peek().cloned()));
+        }
+        Ok(expr)
     }
     pub fn parse(&mut self) -> Vec<Stmt> {
         let mut stmts = vec![];
         while !self.is_at_end() {
-            stmts.push(self.collect());
+            self.statement_reported = false;
+            let stmt = self.collect();
+            // A statement that only parsed by recovering from an illegal token must not be run
+            let stmt = if std::mem::take(&mut self.parser_corrupt) && !matches!(stmt, Stmt::ErrStmt { .. }) {
+                self.error_stmt(ParserError::IllegalStmt(Some(format!("{stmt}"))))
+            } else {
+                stmt
+            };
+            stmts.push(stmt);
             // BUG_FIXED: If var ? or an ErrDecl is returned, this loop never ends
             // BUG_FIXED: Doesn't synchronize on multiline comments
             // BUG_FIXED : Infinte loop on char
@@ -571,9 +741,18 @@ impl Parser {
         }
         stmts
     }
+    /// Parse as a declaration or a statement, tagged with the position of its first token
+    fn collect(&mut self) -> Stmt {
+        let (line, col) = self.peek().map(|t| (t.ln, t.col)).unwrap_or_default();
+        match self.declaration() {
+            // Syntax errors have already been reported with their position
+            err @ Stmt::ErrStmt { .. } => err,
+            stmt => Stmt::Located { line, col, stmt: box stmt },
+        }
+    }
     // TODO: Transform all statement methods to return a Result
     /// Parse as a variable declaration or else a statment
-    fn collect(&mut self) -> Stmt {
+    fn declaration(&mut self) -> Stmt {
         // When panic, call self.synchronize()
         // Declarations can be either a VarDecl or a normal Statement, 
         // we decide that here: 
@@ -582,12 +761,21 @@ impl Parser {
                 Ok(d) => d,
                 Err(err) => { 
                     loc!(format!("Declaration parsing error : {}{}","Parser Error ".bright_cyan(), err));
-                    let d = err.into(); // to leverage type inference for the following macro
+                    let d = self.error_stmt(err);
                     loc!(d);
-                    d // due to this rust can infer the type and use it in the above macro
+                    d
                 },
             }
         } 
+        else if self.matches(&[CONST]) {
+            match self.const_declaration() {
+                Ok(d) => d,
+                Err(err) => {
+                    self.synchronize();
+                    self.error_stmt(err)
+                }
+            }
+        }
         else if self.matches(&[FUN]) {
             self.function_declaration()
         }
@@ -604,7 +792,7 @@ impl Parser {
             Err(err) => {
                 loc!("statement error");
                 self.synchronize();
-                err.into()
+                self.error_stmt(err)
             },
         }
     }
@@ -612,7 +800,8 @@ impl Parser {
     fn function(&mut self) -> Result<Stmt, ParserError> {
         if self.matches(&[IDENTIFIER]) {
             let ident = self.previous.take().expect("matches is infallible");
-            Ok(Stmt::FunDecl { ident, params: self.params()?, body : if self.matches(&[LEFT_BRACE]) {
+            let (params, defaults) = self.params()?;
+            Ok(Stmt::FunDecl { ident, params, defaults, body : if self.matches(&[LEFT_BRACE]) {
                     Box::new(self.block_statement()?)
                 }
                 else {
@@ -623,18 +812,29 @@ impl Parser {
             Err(ParserError::InvalidFuncDecl)
         }
     }
-    fn params(&mut self) -> Result<Vec<Token>, ParserError> {
+    /// params           → param ( "," param )* ;
+    /// param            → IDENTIFIER ( "=" expression )? ;
+    /// Returns the parameters along with the defaults of the trailing ones, once a parameter has a
+    /// default every parameter after it needs one too
+    fn params(&mut self) -> Result<(Vec<Token>, Vec<Box<Expression>>), ParserError> {
         let mut params = vec![];
+        let mut defaults = vec![];
         self.consume(LEFT_PAREN)?;
         if self.matches(&[RIGHT_PAREN]) {
-            return Ok(Vec::new())
+            return Ok((Vec::new(), Vec::new()))
         }
         loop {
             // We don't want a keyword as a fn param
-            params.push(self.consume(IDENTIFIER)?.ok_or_else(|| ParserError::InvalidFuncDecl)?);
+            let param = self.consume(IDENTIFIER)?.ok_or_else(|| ParserError::InvalidFuncDecl)?;
+            if self.matches(&[EQUAL]) {
+                defaults.push(self.expression()?);
+            } else if !defaults.is_empty() {
+                return Err(ParserError::MissingDefault(param.lexeme));
+            }
+            params.push(param);
             if params.len() > 254 {
                 let ref last = params[params.len()-1];
-                Lox::report_syntax_err(last.ln, last.col, "Too many arguments to function".to_string());
+                self.syntax_error(last.ln, last.col, "Too many arguments to function".to_string());
                 // Relax this to continue parsing in case of too many args
                 return Err(ParserError::TooManyArgs(Some(last.clone())));
             }
@@ -650,9 +850,12 @@ impl Parser {
                 return Err(ParserError::InvalidFuncArgs);
             }
         }
-        Ok(params)
+        Ok((params, defaults))
     }
     fn var_declaration(&mut self) -> Result<Stmt, ParserError> {
+        if self.matches(&[LEFT_PAREN]) {
+            return self.destructure_declaration();
+        }
         if self.matches(&[IDENTIFIER])  {
             let name_token = self.previous.take().expect("matches is infallible");
             let name = name_token.lexeme;
@@ -669,11 +872,42 @@ impl Parser {
                 Ok(Stmt::VarDecl{ name, initializer: None })
             }
         }   
+        else if let Some(token) = self.peek() && token.is_keyword() {
+           let keyword = token.lexeme.clone();
+           self.synchronize();
+           Err(ParserError::KeywordMisuse(keyword, "a variable name"))
+        }
         else {
            self.synchronize();
            Err(ParserError::IllegalStmt(Some("Missing variable identifer".into())))
         }
     }
+    /// `const name = expression;` the `const` has already been consumed.
+    /// A const has to be initialized since it can never be assigned later
+    fn const_declaration(&mut self) -> Result<Stmt, ParserError> {
+        let name = self.consume(IDENTIFIER)?.expect("consume returns the matched token").lexeme;
+        self.consume(EQUAL)?;
+        let initializer = self.parse_expression()?;
+        self.consume(SEMICOLON)?;
+        Ok(Stmt::ConstDecl { name, initializer })
+    }
+    /// `var (a, b) = expression;` the `var (` has already been consumed
+    fn destructure_declaration(&mut self) -> Result<Stmt, ParserError> {
+        let mut names = vec![];
+        loop {
+            let name = self.consume(IDENTIFIER)?.expect("consume returns the matched token");
+            names.push(name.lexeme);
+            if !self.matches(&[COMMA]) {
+                break;
+            }
+        }
+        self.consume(RIGHT_PAREN)?;
+        // Unlike a plain declaration there's nothing to destructure without an initializer
+        self.consume(EQUAL)?;
+        let initializer = self.parse_expression()?;
+        self.consume(SEMICOLON)?;
+        Ok(Stmt::Destructure { names, initializer })
+    }
     /// Parse as a statement, converting ParserErrors into ErrStmt enclosing the ParserError
     fn statement(&mut self) -> Stmt {
         if self.matches(&[COMMENT, MULTI_LINE_COMMENT]) {
@@ -699,6 +933,22 @@ impl Parser {
         {
             self.break_statement()
         }
+        else if self.matches(&[RETURN])
+        {
+            self.return_statement()
+        }
+        else if self.matches(&[REPEAT])
+        {
+            self.repeat_statement()
+        }
+        else if self.matches(&[TRY])
+        {
+            self.try_statement()
+        }
+        else if self.matches(&[SWITCH])
+        {
+            self.switch_statement()
+        }
         else {
             self.expression_statement()
         };
@@ -707,12 +957,16 @@ impl Parser {
             Err(err) => {
                 loc!("statement error");
                 self.synchronize();
-                err.into()
+                self.error_stmt(err)
             },
         }
     }
     fn for_statement(&mut self) ->  Result<Stmt, ParserError> {
         self.consume(LEFT_PAREN)?;
+        // `for (x in ...)` or `for (i, x in ...)`
+        if matches!(self.peek_types::<4>(), [Some(IDENTIFIER), Some(IN), ..] | [Some(IDENTIFIER), Some(COMMA), Some(IDENTIFIER), Some(IN)]) {
+            return self.for_in_statement();
+        }
         let initializer : Option<Stmt> = if self.matches(&[SEMICOLON])
         {   
             None
@@ -723,23 +977,34 @@ impl Parser {
         else {
             Some(self.expression_statement()?)
         };
+        // The `;` after the condition is consumed below even when the condition is empty,
+        // its position stands in for the missing condition
         let condition : Option<Box<Expression>> = 
-          if self.matches(&[SEMICOLON])
+          if self.peek().is_some_and(|t| t.r#type == SEMICOLON)
           {
             None
           } else {
             Some(self.parse_expression()?)
           }
         ;
+        // `for (...; i = 5; ...)` assigns on every iteration, most likely `==` was meant
+        if let Some(cond) = &condition && let Expression::Assignment(AssignmentExpr { name, .. }) = &**cond {
+            self.warnings.push(Diagnostic {
+                line: name.ln,
+                col: name.col,
+                message: format!("for loop condition assigns to `{}`, did you mean `==`?", name.lexeme),
+            });
+        }
         let cond_pos = self.consume(SEMICOLON).map_err(|_err| ParserError::MissingOperand(SEMICOLON))?.expect("ICE: Expected `;` here");
         let (cond_ln, cond_col) = (cond_pos.ln, cond_pos.col);
-        let update : Option<Box<Expression>> = if self.matches(&[RIGHT_PAREN]) {
+        let update : Option<Box<Expression>> = if self.peek().is_some_and(|t| t.r#type == RIGHT_PAREN) {
             None
         } else {
             Some(self.parse_expression()?)
         };
         self.consume(RIGHT_PAREN).map_err(|_err| ParserError::MissingOperand(RIGHT_PAREN))?;
         let block : Stmt = self.collect();
+        let braced = matches!(block.inner(), Stmt::Block(_));
         let for_condition = condition.unwrap_or_else(|| {
             let ttrue = Token {r#type : TRUE, ln: cond_ln, col: cond_col, lexeme: "true".to_string()};
             let literal_expr = Literal::new(ttrue).expect("infallible");
@@ -751,16 +1016,50 @@ impl Parser {
             },
             None => block,
         };
-        let while_loop = Stmt::While { condition: for_condition, body: for_block };
+        let else_ = self.loop_else(braced);
+        let while_loop = Stmt::While { condition: for_condition, body: for_block, else_ };
         let for_loop = match initializer {
             Some(init_expr) => Stmt::Block(vec![init_expr, while_loop]), 
             None => while_loop,
         };
         Ok(for_loop)
     }
+    /// The rest of a for-in loop, after its `(`
+    fn for_in_statement(&mut self) -> Result<Stmt, ParserError> {
+        let first = self.consume(IDENTIFIER)?.ok_or(ParserError::ExpectedExpression)?.lexeme;
+        let (index, name) = if self.matches(&[COMMA]) {
+            (Some(first), self.consume(IDENTIFIER)?.ok_or(ParserError::ExpectedExpression)?.lexeme)
+        } else {
+            (None, first)
+        };
+        self.consume(IN)?;
+        let iterable = self.parse_expression()?;
+        self.consume(RIGHT_PAREN)?;
+        let body = box self.collect();
+        Ok(Stmt::ForIn { index, name, iterable, body })
+    }
     fn break_statement(&mut self) ->  Result<Stmt, ParserError> {
+        if self.matches(&[SEMICOLON]) {
+            return Ok(Stmt::Break(None));
+        }
+        let value = self.parse_expression()?;
+        self.consume(SEMICOLON)?;
+        Ok(Stmt::Break(Some(value)))
+    }
+    fn return_statement(&mut self) -> Result<Stmt, ParserError> {
+        if self.matches(&[SEMICOLON]) {
+            return Ok(Stmt::Return(None));
+        }
+        let value = self.parse_expression()?;
         self.consume(SEMICOLON)?;
-        Ok(Stmt::Break)
+        Ok(Stmt::Return(Some(value)))
+    }
+    fn repeat_statement(&mut self) -> Result<Stmt, ParserError> {
+        self.consume(LEFT_PAREN)?;
+        let count = self.parse_expression()?;
+        self.consume(RIGHT_PAREN)?;
+        let body = box self.collect();
+        Ok(Stmt::Repeat { count, body })
     }
     fn while_statement(&mut self) -> Result<Stmt, ParserError> {
         self.consume(LEFT_PAREN)?;
@@ -768,7 +1067,18 @@ impl Parser {
         loc!(format!("if condition -> {}", &condition));
         self.consume(RIGHT_PAREN)?;
         let body = box self.collect();
-        Ok(Stmt::While { condition, body })
+        let else_ = self.loop_else(matches!(body.inner(), Stmt::Block(_)));
+        Ok(Stmt::While { condition, body, else_ })
+    }
+    /// The optional `else` of a `while` or `for` loop. It's only taken after a braced loop body, and
+    /// never when the loop is the then-branch of an `if`, where the `else` belongs to the `if`:
+    /// `if (ready) for (...) print i; else print "not ready";`
+    fn loop_else(&mut self, braced_body: bool) -> Option<Box<Stmt>> {
+        if braced_body && !self.in_then_branch && self.matches(&[ELSE]) {
+            Some(box self.collect())
+        } else {
+            None
+        }
     }
     fn if_statement(&mut self) -> Result<Stmt, ParserError> {
         self.consume(LEFT_PAREN)?;
@@ -776,7 +1086,9 @@ impl Parser {
         loc!(format!("if condition -> {}", &condition));
         self.consume(RIGHT_PAREN)?;
         // let then = self.collect();
+        let outer = std::mem::replace(&mut self.in_then_branch, true);
         let then_ = box self.collect();
+        self.in_then_branch = outer;
         loc!(format!("then branch -> {}", *then_));
         let mut else_ = None;
         // This `else` is bound to the nearest if statement
@@ -784,15 +1096,33 @@ impl Parser {
             else_ = Some(box self.collect());
             loc!(format!("else branch -> {}", else_.as_ref().unwrap()));
         }
+        // `if (true) A; else B;` never runs `B`, and `if (false) A;` never runs `A`
+        if let Expression::Lit(Literal { inner }) = &*condition {
+            let dead = match inner.r#type {
+                TRUE if else_.is_some() => Some("else"),
+                FALSE => Some("then"),
+                _ => None,
+            };
+            if let Some(branch) = dead {
+                self.warnings.push(Diagnostic {
+                    line: inner.ln,
+                    col: inner.col,
+                    message: format!("if condition is always {}, the {branch} branch is unreachable", inner.lexeme),
+                });
+            }
+        }
         Ok(Stmt::IfStmt { condition, then_, else_ })
 
     }
     // We are not making use of Err(ParserError) yet, and just return Ok(ErrStmt) instead
     fn print_statement(&mut self) -> Result<Stmt, ParserError> {
+        if self.matches(&[SEMICOLON]) {
+            return Ok(Stmt::Print(None));
+        }
         let val = self.parse_expression()?;
         // println!("print statement - > {}", val);
         self.consume(SEMICOLON)?;
-        Ok(Stmt::Print(val))
+        Ok(Stmt::Print(Some(val)))
     }
     // We are not making use of Err(ParserError) yet, and just return Ok(ErrStmt) instead
     fn expression_statement(&mut self) -> Result<Stmt, ParserError> {     
@@ -801,14 +1131,60 @@ impl Parser {
         self.consume(SEMICOLON)?;
         Ok(Stmt::ExprStmt(val))
     }
+    fn try_statement(&mut self) -> Result<Stmt, ParserError> {
+        self.consume(LEFT_BRACE)?;
+        let try_block = box Stmt::Block(self.block()?);
+        self.consume(CATCH)?;
+        self.consume(LEFT_PAREN)?;
+        let error_name = self.consume(IDENTIFIER)?.expect("consume returns the matched token").lexeme;
+        self.consume(RIGHT_PAREN)?;
+        self.consume(LEFT_BRACE)?;
+        let catch_block = box Stmt::Block(self.block()?);
+        Ok(Stmt::TryCatch { try_block, error_name, catch_block })
+    }
+    fn switch_statement(&mut self) -> Result<Stmt, ParserError> {
+        self.consume(LEFT_PAREN)?;
+        let discriminant = self.parse_expression()?;
+        self.consume(RIGHT_PAREN)?;
+        self.consume(LEFT_BRACE)?;
+        let (mut cases, mut default) = (vec![], None);
+        loop {
+            if self.matches(&[CASE]) {
+                let value = self.expression()?;
+                self.consume(TERNARYE)?;
+                cases.push((value, self.case_body()));
+            } else if self.matches(&[DEFAULT]) {
+                if default.is_some() {
+                    return Err(ParserError::IllegalStmt(Some("switch has more than one default".into())));
+                }
+                self.consume(TERNARYE)?;
+                default = Some(self.case_body());
+            } else {
+                break;
+            }
+        }
+        self.consume(RIGHT_BRACE)?;
+        Ok(Stmt::Switch { discriminant, cases, default })
+    }
+    /// Statements of a switch case, up to the next `case`, `default` or the closing `}`
+    fn case_body(&mut self) -> Vec<Stmt> {
+        let mut stmts = vec![];
+        while let Some(x) = self.peek() && !matches!(x.r#type, CASE | DEFAULT | RIGHT_BRACE) && !self.is_at_end() {
+            stmts.push(self.collect());
+        }
+        stmts
+    }
     fn block_statement(&mut self) -> Result<Stmt, ParserError> {     
         Ok(Stmt::Block(self.block()?))
     }
     fn block(&mut self) -> Result<Vec<Stmt>, ParserError> {
         let mut block_stmts: Vec<Stmt> = vec![];
+        // An `else` inside braces can't belong to an `if` outside them
+        let outer = std::mem::replace(&mut self.in_then_branch, false);
         while let Some(x) = self.peek() && x.r#type != RIGHT_BRACE && !self.is_at_end() {
             block_stmts.push(self.collect());
         } 
+        self.in_then_branch = outer;
         self.consume(RIGHT_BRACE)?;
         loc!("Block parsed successfully");
         Ok(block_stmts)
@@ -10,6 +10,8 @@ pub enum Stmt {
     FunDecl {
         ident : Token, 
         params: Vec<Token>,
+        /// Default values of the trailing parameters that have one, `fun f(a, b = 1)` has `[1]`
+        defaults: Vec<Box<Expression>>,
         body : Box<Stmt>,
     },
     #[display(fmt = "VarDecl IDENTIFER : '{}', Expression : {:?}", name, initializer)]
@@ -17,13 +19,25 @@ pub enum Stmt {
         name: String,
         initializer: Option<Box<Expression>>,
     },
+    /// `const PI = 3.14;` declares a binding that can't be assigned to afterwards
+    #[display(fmt = "ConstDecl IDENTIFER : '{}', Expression : {}", name, initializer)]
+    ConstDecl {
+        name: String,
+        initializer: Box<Expression>,
+    },
+    /// `var (a, b) = [1, 2];` binds each name to the array element at the same position
+    #[display(fmt = "Destructure {:?} = {}", names, initializer)]
+    Destructure {
+        names: Vec<String>,
+        initializer: Box<Expression>,
+    },
     /// An expression statement lets you place an expression where a statement is expected
     /// They exist to evaluate expressions that may have side effects
     #[display(fmt = "ExprStmt [{}]", "_0")]
     ExprStmt(Box<Expression>),
-    /// A print statement evaluaets an expression and prints to console
-    #[display(fmt = "PrintStmt : [{}]", "*_0")]
-    Print(Box<Expression>),
+    /// A print statement evaluaets an expression and prints to console, `print;` prints an empty line
+    #[display(fmt = "PrintStmt : [{}]", r#"_0.as_ref().map(|e| e.to_string()).unwrap_or_default()"#)]
+    Print(Option<Box<Expression>>),
     /// Represents a syntax error, maybe moved to Declaration
     ErrStmt { message: String },
     /// Represents a comment
@@ -48,10 +62,63 @@ pub enum Stmt {
     #[display(fmt = "While Stmt (Condition : {})", condition)]
     While {
         condition: Box<Expression>,
+        body: Box<Stmt>,
+        /// Runs once the condition turns false, but not when the loop is left with `break`
+        else_: Option<Box<Stmt>>,
+    },
+    /// `for (x in iterable) body` runs `body` for each element of an array or char of a string,
+    /// `for (i, x in array) body` also binds the element's index to `i`
+    #[display(fmt = "ForIn Stmt (Iterable : {})", iterable)]
+    ForIn {
+        index: Option<String>,
+        name: String,
+        iterable: Box<Expression>,
+        body: Box<Stmt>,
+    },
+    /// Run `body` a fixed number of times, `count` is evaluated once before the first iteration
+    #[display(fmt = "Repeat Stmt (Count : {})", count)]
+    Repeat {
+        count: Box<Expression>,
         body: Box<Stmt>
     },
+    /// `break;` or `break value;`, the value is what a `while` expression evaluates to
     #[display(fmt = "Break Stmt")]
-    Break,
+    Break(Option<Box<Expression>>),
+    /// `return;` or `return value;`, ends the enclosing function call with the value, nil if left out
+    #[display(fmt = "Return Stmt")]
+    Return(Option<Box<Expression>>),
+    /// A statement tagged with the position of its first token, so runtime errors can say where they happened
+    #[display(fmt = "{}", stmt)]
+    Located {
+        line: usize,
+        col: usize,
+        stmt: Box<Stmt>,
+    },
+    /// Run `try_block`, if it raises a runtime error bind its message to `error_name` and run `catch_block`
+    #[display(fmt = "TryCatch Stmt (catch {})", error_name)]
+    TryCatch {
+        try_block: Box<Stmt>,
+        error_name: String,
+        catch_block: Box<Stmt>,
+    },
+    /// Run the statements of the first case whose value equals `discriminant`, or `default` if none do.
+    /// Cases don't fall through, a `break` leaves the switch early
+    #[display(fmt = "Switch Stmt (Discriminant : {})", discriminant)]
+    Switch {
+        discriminant: Box<Expression>,
+        cases: Vec<(Box<Expression>, Vec<Stmt>)>,
+        default: Option<Vec<Stmt>>,
+    },
+}
+
+impl Stmt {
+    /// The statement without its source position
+    pub fn inner(&self) -> &Stmt {
+        match self {
+            Stmt::Located { stmt, .. } => stmt.inner(),
+            stmt => stmt,
+        }
+    }
 }
 
 // Since we are using Ok(ErrStmt) instead of Err(ParserError) at some stages : expression_statement and print_statement
@@ -1,3 +1,4 @@
 pub mod printer;
 pub mod evaluate;
-pub mod lox_callable;
\ No newline at end of file
+pub mod lox_callable;
+pub mod visitor;
\ No newline at end of file
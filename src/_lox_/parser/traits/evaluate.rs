@@ -2,13 +2,14 @@ use std::cell::RefCell;
 use std::cmp::Ordering;
 use std::rc::Rc;
 
-use crate::interpreter::{self, Environment, Memory, Interpreter};
+use crate::interpreter::{self, Environment, Memory, Interpreter, ResolvedVar};
 use crate::parser::error::{EvalError, RuntimeError};
 use crate::parser::expressions::*;
 use crate::parser::value::ValueResult;
 use crate::parser::value::Value;
-use crate::tokenizer::token_type::TokenType::*;
-use crate::{loc, Lox};
+use crate::tokenizer::token::Token;
+use crate::tokenizer::token_type::TokenType::{self, *};
+use crate::loc;
 pub trait Evaluate {
     type Environment: Memory;
     fn eval(
@@ -27,27 +28,8 @@ impl Evaluate for Expression {
         env: &Self::Environment,
         int: &mut interpreter::Interpreter,
     ) -> ValueResult {
-        match self {
-            Expression::CommaExpr(expr_list) => {
-                // Comma expressions evaluate the list, discarding all results uptil the last one
-                expr_list.iter().enumerate().for_each(|(idx, item)| {
-                    if idx != expr_list.len() - 1 {
-                        // eval and discard
-                        match item.eval(env, int) {
-                            Ok(_x) => { /*println!("Evaluating {item:?} got -> {x:?}")*/ }
-                            Err(e) => println!("Evaluating {item:?} got error -> {e:?}"),
-                        }
-                    }
-                });
-                if let Some(last) = expr_list.last() {
-                    last.eval(env, int)
-                } else {
-                    Err(EvalError::InvalidExpr(
-                        self.clone(),
-                        Some(format!("Cannot evaluate comma expression {:?}", expr_list)),
-                    ))
-                }
-            }
+        let result = match self {
+            Expression::CommaExpr(expr_list) => eval_comma(expr_list, env, int),
             Expression::TernExpr(ternary) => ternary.eval(env,int),
             Expression::BinExpr(bin_exp) => bin_exp.eval(env,int),
             Expression::UnExpr(un_exp) => un_exp.eval(env,int),
@@ -59,102 +41,209 @@ impl Evaluate for Expression {
             // For now let's throw an error on error production evaluations
             Expression::Error(_err) => Err(EvalError::ErrorProduction),
             // We include this because user may hit `a` and expect to see a value just like in python
-            Expression::Variable(t) => {
-                // We want the syntax tree to reflect that an l-value isn’t evaluated like a normal expression.
-                // TODO: What should a variable evaluate to?
-                match env.get(t) {
-                    Ok(v) => {
-                        if let Some(x) = v {
-                            Ok(x.to_owned())
-                        } else {
-                            // Ok(None) means variable was found in storage, but not initialized therefore it's an error
-                            // to use it before initialization
-                            Err(EvalError::VariableEval(RuntimeError::UndefinedVar(
-                                t.lexeme.clone(),
-                            )))
-                        }
-                    }
-                    // undefined
-                    Err(err) => {
-                        loc!(format!("Error on variable.eval() {err}"));
-                        Err(EvalError::VariableEval(err))
-                    }
+            Expression::Variable(t) => eval_variable(t, env),
+            Expression::Array(items) => {
+                let mut values = Vec::with_capacity(items.len());
+                for item in items {
+                    values.push(item.eval(env, int)?);
                 }
+                Ok(values.into())
             }
             Expression::LogicOr(l) => l.eval(env,int),
             Expression::LogicAnd(l) => l.eval(env,int),
-            Expression::Call(
-                fncallexpr @ FnCallExpr {
-                    callee,
-                    paren: _, // TODO: use this for error reporting
-                    args,
-                },
-            ) => {
-                // We allow for Fn(1)(2)(3).. so the callee for (2) is actually Fn(1) and the callee for (3) is actually Fn(1)(2)
-
-                // TODO : In case of an indentifier or Variable(Token), what modifications
-                // should we make to Variable(Token)'s evaluation implementation for this
-                // to work correctly?
-
-                // For now, we stay consistent with our overall pattern and "eval" whatever the callee expression is
-                let evaluated_callee: Value =
-                    if let Expression::Variable(ident) = &**callee {
-                        let lox_fn = match env.get(&ident) {
-                            // Ok expects a LoxFunction to be defined at this ident key
-                            Ok(v) => {
-                                if let Some(x) = v {
-                                    Ok(x.to_owned())
-                                } else {
-                                    // Ok(None) means variable was found in storage, but not initialized therefore it's an error
-                                    // to use it before initialization
-                                    // Note: This cannot happen for functions as they are declared and defined at one go
-                                    panic!(
-                                    "ICE: Functions cannot be declared but not defined"
-                                );
-                                }
-                            }
-                            // undefined
-                            Err(_err) => {
-                                loc!(format!("Error on function.eval() {_err}"));
-                                Err(EvalError::FunctionUndefined(
-                                    RuntimeError::UndefinedFunc(ident.lexeme.clone()),
-                                ))
-                            }
-                        };
-                        lox_fn?
-                    } else {
-                        callee.eval(env,int)?
-                    };
-                let mut args_result: Vec<ValueResult> = vec![];
-                for arg in args.iter() {
-                    args_result.push(arg.eval(env,int));
-                }
-                if args_result.iter().any(|res| res.is_err()) {
-                    return Err(EvalError::FunctionArgError);
+            Expression::Call(_) | Expression::Get(_) | Expression::OptionalGet(_) | Expression::Index(_) => {
+                Ok(eval_chain(self, env, int)?.unwrap_or(Value::Nil))
+            }
+            Expression::While(WhileExpr { condition, body }) => {
+                let loop_env = Rc::new(RefCell::new(Environment::loop_enclosed_by(Rc::clone(env))));
+                match int.run_while(condition, body, env, loop_env)? {
+                    Value::Break(value) => Ok(*value),
+                    // Carried out of the expression to the statement holding it, see `Interpreter::execute`
+                    Value::Return(value) => Err(EvalError::ReturnFromExpression(value)),
+                    _ => Ok(Value::Nil),
                 }
-                let args = args_result
-                    .into_iter()
-                    .map(|x| x.unwrap())
-                    .collect::<Vec<_>>();
-
-                if let Value::Function(lox_fn) = evaluated_callee {
-                    if lox_fn.arity() != args.len() {
-                        Lox::report_runtime_err(format!(
-                            "Expected {} but got {} arguments",
-                            lox_fn.arity(),
-                            args.len()
-                        ));
-                        return Err(EvalError::FunctionArgError);
-                    }
-                    lox_fn.call(args, int)
-                } else {
-                    return Err(EvalError::FunctionCallError(fncallexpr.location()));
+            }
+        };
+        if int.tracing() {
+            if let Ok(value) = &result {
+                let shown = int.format_value(value);
+                int.trace(&format!("  {self} => {shown}"));
+            }
+        }
+        result
+    }
+}
+/// Comma expressions evaluate the list, discarding all results uptil the last one
+/// An error in any of them is the error of the whole expression
+fn eval_comma(expr_list: &[Box<Expression>], env: &LoxEnvironment, int: &mut Interpreter) -> ValueResult {
+    if let Some((last, rest)) = expr_list.split_last() {
+        for item in rest {
+            // eval and discard
+            item.eval(env, int)?;
+        }
+        last.eval(env, int)
+    } else {
+        Err(EvalError::InvalidExpr(
+            Expression::CommaExpr(expr_list.to_vec()),
+            Some(format!("Cannot evaluate comma expression {:?}", expr_list)),
+        ))
+    }
+}
+
+/// Look up the variable named by `ident`. Reading a declared but uninitialized variable is an error
+fn eval_variable(ident: &Token, env: &LoxEnvironment) -> ValueResult {
+    // We want the syntax tree to reflect that an l-value isn’t evaluated like a normal expression.
+    match env.get(ident) {
+        Ok(ResolvedVar::Defined(value)) => Ok(value),
+        // Declared but never assigned, it's an error to use it before initialization
+        Ok(ResolvedVar::Uninitialized) => Err(EvalError::VariableEval(RuntimeError::UndefinedVar(
+            ident.lexeme.clone(),
+        ))),
+        // undefined
+        Err(err) => {
+            loc!(format!("Error on variable.eval() {err}"));
+            Err(EvalError::VariableEval(err))
+        }
+    }
+}
+
+/// Evaluate a chain of calls, indexes and property accesses like `a?.b[0].c()`. `None` when a `?.`
+/// found nil, which skips the rest of the chain: `nil?.x.y` is nil rather than an error
+fn eval_chain(expr: &Expression, env: &LoxEnvironment, int: &mut Interpreter) -> Result<Option<Value>, EvalError> {
+    match expr {
+        // There are no instances yet so nothing has properties
+        Expression::Get(get) => match eval_chain(&get.object, env, int)? {
+            Some(object) => Err(EvalError::NoProperties(object.type_name())),
+            None => Ok(None),
+        },
+        Expression::OptionalGet(get) => match eval_chain(&get.object, env, int)? {
+            Some(Value::Nil) | None => Ok(None),
+            Some(object) => Err(EvalError::NoProperties(object.type_name())),
+        },
+        Expression::Index(index) => match eval_chain(&index.object, env, int)? {
+            Some(object) => eval_index(object, &index.index, env, int).map(Some),
+            None => Ok(None),
+        },
+        // A variable callee is looked up as a function, see `eval_call`
+        Expression::Call(call) if !matches!(*call.callee, Expression::Variable(_)) => {
+            match eval_chain(&call.callee, env, int)? {
+                Some(callee) => call_value(call, callee, env, int).map(Some),
+                None => Ok(None),
+            }
+        }
+        Expression::Call(call) => eval_call(call, env, int).map(Some),
+        other => other.eval(env, int).map(Some),
+    }
+}
+
+/// `object[index]` for arrays, the index has to be a whole number
+fn eval_index(object: Value, index: &Expression, env: &LoxEnvironment, int: &mut Interpreter) -> ValueResult {
+    let index = index.eval(env, int)?;
+    let items = match &object {
+        Value::Array(items) => items.borrow(),
+        other => return Err(EvalError::NotIndexable(other.type_name())),
+    };
+    let Some(n) = index.is_numeric() else {
+        return Err(EvalError::InvalidArgument(format!(
+            "Array index must be a number, found a {}",
+            index.type_name()
+        )));
+    };
+    if n.fract() != 0.0 {
+        return Err(EvalError::InvalidArgument(format!("array index must be a whole number, found {n}")));
+    }
+    match items.get(n as usize) {
+        Some(item) if n >= 0.0 => Ok(item.clone()),
+        _ => Err(EvalError::IndexOutOfBounds(n, items.len())),
+    }
+}
+
+/// How a call's callee is named in error messages and tracebacks
+fn callee_name(callee: &Expression) -> String {
+    match callee {
+        Expression::Variable(ident) => ident.lexeme.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Evaluate the callee, then the arguments left to right, then call it
+fn eval_call(fncallexpr: &FnCallExpr, env: &LoxEnvironment, int: &mut Interpreter) -> ValueResult {
+    let FnCallExpr {
+        callee,
+        paren: _, // TODO: use this for error reporting
+        args: _,
+    } = fncallexpr;
+    // We allow for Fn(1)(2)(3).. so the callee for (2) is actually Fn(1) and the callee for (3) is actually Fn(1)(2)
+
+    // TODO : In case of an indentifier or Variable(Token), what modifications
+    // should we make to Variable(Token)'s evaluation implementation for this
+    // to work correctly?
+
+    // For now, we stay consistent with our overall pattern and "eval" whatever the callee expression is
+    let evaluated_callee: Value =
+        if let Expression::Variable(ident) = &**callee {
+            let lox_fn = match env.get(&ident) {
+                // Expects a LoxFunction to be defined at this ident key
+                Ok(ResolvedVar::Defined(value)) => Ok(value),
+                // Functions are declared and defined at one go, this is a `var f;` that was never assigned
+                Ok(ResolvedVar::Uninitialized) => Err(EvalError::VariableEval(RuntimeError::UndefinedVar(
+                    ident.lexeme.clone(),
+                ))),
+                // undefined
+                Err(_err) => {
+                    loc!(format!("Error on function.eval() {_err}"));
+                    Err(EvalError::FunctionUndefined(
+                        RuntimeError::UndefinedFunc(ident.lexeme.clone()),
+                    ))
                 }
+            };
+            lox_fn?
+        } else {
+            callee.eval(env,int)?
+        };
+    call_value(fncallexpr, evaluated_callee, env, int)
+}
+
+/// Evaluate the arguments left to right, then call the already evaluated callee
+fn call_value(fncallexpr: &FnCallExpr, evaluated_callee: Value, env: &LoxEnvironment, int: &mut Interpreter) -> ValueResult {
+    let FnCallExpr { callee, paren: _, args } = fncallexpr;
+    let mut arg_values = Vec::with_capacity(args.len());
+    for (idx, arg) in args.iter().enumerate() {
+        match arg.eval(env, int) {
+            Ok(value) => arg_values.push(value),
+            Err(err) => {
+                return Err(EvalError::FunctionArgError(callee_name(callee), idx + 1, fncallexpr.paren.ln, Box::new(err)));
             }
         }
     }
+    let args = arg_values;
+
+    if let Some(lox_fn) = evaluated_callee.as_callable() {
+        let (min, max) = (lox_fn.min_arity(), lox_fn.arity());
+        // Lox functions given only some of their arguments are partially applied, see LoxFunction::call
+        let partial = !lox_fn.is_native() && !args.is_empty();
+        if args.len() < min && !partial || args.len() > max {
+            let expected = if min == max { format!("{max}") } else { format!("{min} to {max}") };
+            return Err(EvalError::InvalidArgument(format!(
+                "Expected {} but got {} arguments",
+                expected,
+                args.len()
+            )));
+        }
+        if lox_fn.is_native() {
+            return lox_fn.call(args, int);
+        }
+        int.call_stack.push((callee_name(callee), fncallexpr.paren.ln));
+        let result = lox_fn.call(args, int);
+        int.call_stack.pop();
+        result
+    } else {
+        Err(EvalError::FunctionCallError(fncallexpr.location()))
+    }
 }
-// logical operators short circuit in rust so we can make use of that
+
+// logical operators short circuit in rust so we can make use of that: the right operand
+// (and any side effect it has) is only evaluated when the left one doesn't decide the result
 // https://stackoverflow.com/questions/53644809/do-logical-operators-short-circuit-in-rust
 // https://doc.rust-lang.org/reference/expressions/operator-expr.html#lazy-boolean-operators
 impl Evaluate for AndExpr {
@@ -165,7 +254,7 @@ impl Evaluate for AndExpr {
         int: &mut interpreter::Interpreter,
     ) -> ValueResult {
         Ok(
-            (self.left.eval(env,int)?.is_truthy() && self.right.eval(env,int)?.is_truthy())
+            (self.left.eval(env,int)?.is_truthy_in(int) && self.right.eval(env,int)?.is_truthy_in(int))
                 .into(),
         )
     }
@@ -178,9 +267,8 @@ impl Evaluate for OrExpr {
         env: &Self::Environment,
         int: &mut interpreter::Interpreter,
     ) -> ValueResult {
-        // Ok((self.left.eval(env,int)?.is_truthy() || panic!("cannot panic this if left true")).into())
         Ok(
-            (self.left.eval(env,int)?.is_truthy() || self.right.eval(env,int)?.is_truthy())
+            (self.left.eval(env,int)?.is_truthy_in(int) || self.right.eval(env,int)?.is_truthy_in(int))
                 .into(),
         )
     }
@@ -203,9 +291,8 @@ impl Evaluate for AssignmentExpr {
         match env.put(name, rval.clone()) {
             // print a = 2 should print "2"
             Ok(()) => Ok(rval),
-            Err(err) => {
-                loc!(format!("{err}"));
-                Lox::report_runtime_err(format!("{err}"));
+            Err(err @ RuntimeError::AssignToConst(_)) => Err(EvalError::VariableEval(err)),
+            Err(_) => {
                 Err(EvalError::InvalidExpr(
                     Expression::Assignment(self.clone()),
                     Some("Cannot assign as variable not declared. Consider declaring with `var` first ".into()),
@@ -223,11 +310,15 @@ impl Evaluate for TernaryExpr {
         env: &Self::Environment,
         int: &mut interpreter::Interpreter,
     ) -> ValueResult {
-        // TernaryExpr { condition : Box<expr> , if_true : Box<expr>, if_false : Box<expr> }
-        let condition = self.condition.eval(env,int)?;
-        let condition = condition.is_truthy();
-        let result = [&self.if_false, &self.if_true][condition as usize];
-        result.eval(env,int)
+        let condition = self.condition.eval(env, int).map_err(|err| {
+            EvalError::TernaryCondition(Box::new(err), self.question.ln, self.question.col)
+        })?;
+        // Only the chosen branch is evaluated, so the other branch's side effects never happen
+        if condition.is_truthy_in(int) {
+            self.if_true.eval(env, int)
+        } else {
+            self.if_false.eval(env, int)
+        }
     }
 }
 
@@ -243,112 +334,18 @@ impl Evaluate for BinaryExpr {
         let left = self.left.eval(env,int)?;
         let right = self.right.eval(env,int)?;
         match self.operator.r#type {
-            MINUS => {
-                if let Some((lval, rval)) = left.is_numeric().and_then(|lval| {
-                    if let Some(rval) = right.is_numeric() {
-                        return Some((lval, rval));
-                    }
-                    None
-                }) {
-                    Ok(Value::Double(lval - rval))
-                } else {
-                    Err(EvalError::InvalidExpr(
-                        err_exp,
-                        Some("Cannot subtract this binexp".to_string()),
-                    ))
-                }
+            PLUS | MINUS if matches!(left, Value::Char(_)) && right.is_string().is_none() => {
+                char_arithmetic(&left, &right, self.operator.r#type, err_exp)
             }
-            MODULUS => match (left.is_numeric(), right.is_numeric()) {
-                (Some(lval), Some(rval)) => Ok(Value::from(lval % rval)),
-                _ => Err(EvalError::InvalidExpr(
-                    err_exp,
-                    Some("Cannot apply modulo to this binexp".to_string()),
-                )),
-            },
-            SLASH => {
-                if let Some((lval, rval)) = left.is_numeric().and_then(|lval| {
-                    if let Some(rval) = right.is_numeric() {
-                        return Some((lval, rval));
-                    }
-                    None
-                }) {
-                    if rval == 0.0 {
-                        Err(EvalError::DivideByZero(err_exp))
-                    } else {
-                        Ok(Value::Double(lval / rval))
-                    }
-                } else {
-                    Err(EvalError::InvalidExpr(
-                        err_exp,
-                        Some("Cannot divide this binexp".to_string()),
-                    ))
-                }
-            }
-            STAR => {
-                if let Some((lval, rval)) = left.is_numeric().and_then(|lval| {
-                    if let Some(rval) = right.is_numeric() {
-                        return Some((lval, rval));
-                    }
-                    None
-                }) {
-                    Ok(Value::Double(lval * rval))
-                } else {
-                    Err(EvalError::InvalidExpr(
-                        err_exp,
-                        Some("Cannot multiply this binexp".to_string()),
-                    ))
-                }
+            MINUS | STAR | SLASH | MODULUS => {
+                numeric_binop(&left, &right, self.operator.r#type, err_exp)
             }
-            PLUS => {
-                if let Some((lval, rval)) = left.is_numeric().and_then(|lval| {
-                    if let Some(rval) = right.is_numeric() {
-                        return Some((lval, rval));
-                    }
-                    None
-                }) {
-                    return Ok(Value::Double(lval + rval));
-                }
-                // Another approach for mutliple Options
-                match (left.is_string(), right.is_string()) {
-                    (Some(lstr), Some(rstr)) => {
-                        // into_owned moves data out of the Cow
-                        // This should be fine as once we eval a binexp, we won't need the value
-                        let mut l = lstr.into_owned();
-                        l.push_str(&rstr);
-                        return Ok(Value::String(l.to_owned()));
-                    }
-                    (Some(lstr), None) => {
-                        let mut l = lstr.into_owned();
-                        if let Some(n) = right.is_numeric() {
-                            l.push_str(&(n.to_string()));
-                            return Ok(Value::String(l.to_owned()));
-                        } else {
-                            return Err(EvalError::InvalidExpr(
-                                err_exp,
-                                Some("Cannot add this binexp".to_string()),
-                            ));
-                        }
-                    }
-                    (None, Some(rstr)) => {
-                        let r = rstr.into_owned();
-                        if let Some(n) = left.is_numeric() {
-                            let mut x = n.to_string();
-                            x.push_str(&r);
-                            return Ok(Value::String(x.to_owned()));
-                        } else {
-                            return Err(EvalError::InvalidExpr(
-                                err_exp,
-                                Some("Cannot add this binexp".to_string()),
-                            ));
-                        }
-                    }
-                    _ => {
-                        return Err(EvalError::InvalidExpr(
-                            err_exp,
-                            Some("Cannot add this binexp".to_string()),
-                        ))
-                    }
-                }
+            PLUS => add(&left, &right, int.strict_concat(), err_exp),
+            // NaN is unordered, so every comparison with it is false except `!=`
+            GREATER | GREATER_EQUAL | LESS | LESS_EQUAL | EQUAL_EQUAL | BANG_EQUAL
+                if matches!((&left, &right), (Value::Double(l), Value::Double(r)) if l.is_nan() || r.is_nan()) =>
+            {
+                Ok(Value::from(self.operator.r#type == BANG_EQUAL))
             }
             GREATER => match left.partial_cmp(&right) {
                 Some(o) => Ok(Value::from(o == Ordering::Greater)),
@@ -380,15 +377,16 @@ impl Evaluate for BinaryExpr {
                     Some(format!("Cannot compare {left:?} with {right:?}")),
                 )),
             },
-            EQUAL_EQUAL => match left.partial_cmp(&right) {
-                Some(o) => Ok(Value::from(o == Ordering::Equal)),
+            // Numbers compare exactly, so `0.1 + 0.2 == 0.3` is false. `approx_eq` compares within a tolerance
+            EQUAL_EQUAL => match left.lox_eq(&right) {
+                Some(eq) => Ok(Value::from(eq)),
                 None => Err(EvalError::InvalidExpr(
                     err_exp,
                     Some(format!("Cannot compare {left:?} with {right:?}")),
                 )),
             },
-            BANG_EQUAL => match left.partial_cmp(&right) {
-                Some(o) => Ok(Value::from(!(o == Ordering::Equal))),
+            BANG_EQUAL => match left.lox_eq(&right) {
+                Some(eq) => Ok(Value::from(!eq)),
                 None => Err(EvalError::InvalidExpr(
                     err_exp,
                     Some(format!("Cannot compare {left:?} with {right:?}")),
@@ -399,6 +397,101 @@ impl Evaluate for BinaryExpr {
     }
 }
 
+/// `+` on anything but a char on the left, decided in this order:
+/// 1. two numbers add
+/// 2. two strings concatenate
+/// 3. a string and one other value concatenate if [concat_operand] gives text for the other value,
+///    otherwise it's an error naming the type that can't be concatenated
+/// 4. anything else is an error naming both types, e.g. `true + false`
+///
+/// Strings never turn into numbers, `"1" + 2` is `"12"`. Convert with `int` to add numeric strings
+fn add(left: &Value, right: &Value, strict: bool, err_exp: Expression) -> ValueResult {
+    let joined = match (left, right) {
+        (Value::Double(_), Value::Double(_)) => return numeric_binop(left, right, PLUS, err_exp),
+        (Value::String(l), Value::String(r)) => Some(format!("{l}{r}")),
+        (Value::String(l), other) => concat_operand(other, strict).map(|r| format!("{l}{r}")),
+        (other, Value::String(r)) => concat_operand(other, strict).map(|l| format!("{l}{r}")),
+        _ => return numeric_binop(left, right, PLUS, err_exp),
+    };
+    match joined {
+        Some(joined) => Ok(Value::String(joined.into())),
+        None => {
+            let other = if left.is_string().is_some() { right } else { left };
+            Err(EvalError::InvalidExpr(
+                err_exp,
+                Some(format!("Cannot concatenate string and {}", other.type_name())),
+            ))
+        }
+    }
+}
+
+/// Text for the non-string side of a string `+`. Chars always concatenate, numbers, bools and nil
+/// only when concatenation isn't strict, see [Interpreter::set_strict_concat](interpreter::Interpreter::set_strict_concat)
+fn concat_operand(value: &Value, strict: bool) -> Option<String> {
+    match value {
+        Value::Char(c) => Some(c.to_string()),
+        _ if strict => None,
+        Value::Double(n) => Some(Value::Double(*n).to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        Value::Nil => Some("nil".to_string()),
+        _ => None,
+    }
+}
+
+/// Apply the arithmetic operator `op` to two numeric operands. If either operand is not a number
+/// the error names both operand types. Division and modulo by zero are reported as [EvalError::DivideByZero].
+///
+/// `%` is the truncated remainder, same as Rust's `%` on floats: the result takes the sign of the
+/// dividend, so `-7 % 3 == -1` and `7 % -3 == 1`
+fn numeric_binop(left: &Value, right: &Value, op: TokenType, err_exp: Expression) -> ValueResult {
+    let (lval, rval) = match (left.is_numeric(), right.is_numeric()) {
+        (Some(lval), Some(rval)) => (lval, rval),
+        _ => {
+            let verb = match op {
+                MINUS => "subtract",
+                PLUS => "add",
+                STAR => "multiply",
+                SLASH => "divide",
+                _ => "apply modulo to",
+            };
+            return Err(EvalError::InvalidExpr(
+                err_exp,
+                Some(format!("Cannot {verb} {} and {}", left.type_name(), right.type_name())),
+            ));
+        }
+    };
+    match op {
+        MINUS => Ok(Value::Double(lval - rval)),
+        PLUS => Ok(Value::Double(lval + rval)),
+        STAR => Ok(Value::Double(lval * rval)),
+        SLASH | MODULUS if rval == 0.0 => Err(EvalError::DivideByZero(err_exp)),
+        SLASH => Ok(Value::Double(lval / rval)),
+        MODULUS => Ok(Value::Double(lval % rval)),
+        _ => unreachable!("ICE: {op:?} is not an arithmetic operator"),
+    }
+}
+
+/// `char + number` and `char - number` shift the code point, `char - char` is the distance between
+/// two code points. Anything else is reported the same way [numeric_binop] does
+fn char_arithmetic(left: &Value, right: &Value, op: TokenType, err_exp: Expression) -> ValueResult {
+    match (left, right, op) {
+        (Value::Char(l), Value::Char(r), MINUS) => Ok(Value::Double(*l as u32 as f64 - *r as u32 as f64)),
+        (Value::Char(c), Value::Double(n), PLUS | MINUS) => {
+            let code_point = if op == PLUS { *c as u32 as f64 + n } else { *c as u32 as f64 - n };
+            if code_point.fract() == 0.0 && code_point >= 0.0 {
+                if let Some(shifted) = char::from_u32(code_point as u32) {
+                    return Ok(Value::Char(shifted));
+                }
+            }
+            Err(EvalError::InvalidExpr(
+                err_exp,
+                Some(format!("{code_point} is not a valid char code point")),
+            ))
+        }
+        _ => numeric_binop(left, right, op, err_exp),
+    }
+}
+
 impl Evaluate for UnaryExpr {
     type Environment = LoxEnvironment;
 
@@ -409,22 +502,27 @@ impl Evaluate for UnaryExpr {
     ) -> ValueResult {
         let right = self.operand.eval(env,int)?;
         let result = match self.operator.r#type {
-            BANG => Value::Bool(!right.is_truthy()),
+            BANG => Value::Bool(!right.is_truthy_in(int)),
             MINUS => match right {
                 Value::Double(rval) => Value::Double(-rval),
                 _ => {
                     return Err(EvalError::InvalidExpr(
                         Expression::UnExpr(self.clone()),
-                        None,
+                        Some(format!("cannot negate a {}", right.type_name())),
                     ))
                 }
             },
-            _ => {
-                return Err(EvalError::InvalidExpr(
-                    Expression::UnExpr(self.clone()),
-                    Some("Cannot evaluate as unary expression".to_string()),
-                ))
-            }
+            PLUS => match right {
+                Value::Double(_) => right,
+                _ => {
+                    return Err(EvalError::InvalidExpr(
+                        Expression::UnExpr(self.clone()),
+                        Some(format!("cannot apply unary plus to a {}", right.type_name())),
+                    ))
+                }
+            },
+            // UnaryExpr::new refuses to build with any other operator
+            op => unreachable!("ICE: UnaryExpr constructed with operator {op:?}"),
         };
         Ok(result)
     }
@@ -436,8 +534,12 @@ impl Evaluate for Literal {
     fn eval(&self, _env: &Self::Environment, _int: &mut Interpreter) -> ValueResult {
         match self.inner.r#type {
             STRING => Ok(self.inner.lexeme.clone().into()),
+            CHAR => Ok(Value::Char(self.inner.lexeme.chars().next().expect(
+                "Internal compiler error: CHAR tokens always hold exactly one char",
+            ))),
             NUMBER => {
-                let n = (&self.inner.lexeme).parse::<f64>().expect(
+                // `parse` doesn't understand digit separators, as in `1_000`
+                let n = self.inner.lexeme.replace('_', "").parse::<f64>().expect(
                     "Internal compiler error: Parsing a Number token as Number is infallible",
                 );
                 Ok(n.into())
@@ -458,6 +560,127 @@ impl Evaluate for Grouping {
         env: &Self::Environment,
         int: &mut interpreter::Interpreter,
     ) -> ValueResult {
-        self.inner.eval(env,int)
+        self.inner.eval(env, int).map_err(|err| match &self.paren {
+            Some(paren) => EvalError::InGrouping(Box::new(err), format!("({})", self.inner), paren.ln, paren.col),
+            None => err,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parser::traits::lox_callable::LoxCallable;
+
+    fn ident(name: &str) -> Token {
+        Token::new(IDENTIFIER, name.into(), 1, 1)
+    }
+    fn number(n: &str) -> Box<Expression> {
+        Box::new(Expression::Lit(Literal::new(Token::new(NUMBER, n.into(), 1, 1)).unwrap()))
+    }
+
+    #[test]
+    fn number_literals_skip_separators() {
+        let mut interpreter = Interpreter::default();
+        let env = Rc::new(RefCell::new(Environment::default()));
+        assert_eq!(number("1_000").eval(&env, &mut interpreter), Ok(Value::Double(1000.0)));
+        assert_eq!(number("1_000.5").eval(&env, &mut interpreter), Ok(Value::Double(1000.5)));
+    }
+
+    #[test]
+    fn variable() {
+        let env = Rc::new(RefCell::new(Environment::default()));
+        env.define("a", Value::Double(1.0));
+        env.declare("b");
+        assert_eq!(eval_variable(&ident("a"), &env), Ok(Value::Double(1.0)));
+        assert_eq!(
+            eval_variable(&ident("b"), &env),
+            Err(EvalError::VariableEval(RuntimeError::UndefinedVar("b".into())))
+        );
+        assert!(matches!(eval_variable(&ident("c"), &env), Err(EvalError::VariableEval(_))));
+    }
+
+    #[test]
+    fn comma() {
+        let env = Rc::new(RefCell::new(Environment::default()));
+        let mut int = Interpreter::default();
+        assert_eq!(eval_comma(&[number("1"), number("2")], &env, &mut int), Ok(Value::Double(2.0)));
+        assert!(eval_comma(&[], &env, &mut int).is_err());
+        let failing = Box::new(Expression::Error(number("1")));
+        assert_eq!(eval_comma(&[failing, number("2")], &env, &mut int), Err(EvalError::ErrorProduction));
+    }
+
+    /// Number of elements in its array argument
+    #[derive(Debug, derive_more::Display)]
+    #[display(fmt = "<native fn: count>")]
+    struct Count;
+    impl LoxCallable for Count {
+        fn call(&self, args: Vec<Value>, _: &mut Interpreter) -> ValueResult {
+            match &args[0] {
+                Value::Array(items) => Ok(Value::Double(items.borrow().len() as f64)),
+                _ => Ok(Value::Nil),
+            }
+        }
+        fn arity(&self) -> usize {
+            1
+        }
+    }
+
+    #[derive(Debug, derive_more::Display)]
+    #[display(fmt = "<native fn: two>")]
+    struct Two;
+    impl LoxCallable for Two {
+        fn call(&self, _: Vec<Value>, _: &mut Interpreter) -> ValueResult {
+            Ok(Value::Nil)
+        }
+        fn arity(&self) -> usize {
+            2
+        }
+    }
+
+    #[test]
+    fn call() {
+        let env = Rc::new(RefCell::new(Environment::default()));
+        env.define("xs", vec![Value::Double(1.0), Value::Double(2.0)].into());
+        let mut int = Interpreter::default();
+        let call = |callee: &str, args: Vec<Box<Expression>>| FnCallExpr {
+            callee: Box::new(Expression::Variable(ident(callee))),
+            paren: Token::new(RIGHT_PAREN, ")".into(), 1, 10),
+            args,
+        };
+        env.define("count", Value::Function(Rc::new(Count)));
+        assert_eq!(
+            eval_call(&call("count", vec![Box::new(Expression::Variable(ident("xs")))]), &env, &mut int),
+            Ok(Value::Double(2.0))
+        );
+        assert_eq!(
+            eval_call(&call("count", vec![]), &env, &mut int),
+            Err(EvalError::InvalidArgument("Expected 1 but got 0 arguments".into()))
+        );
+        assert_eq!(
+            eval_call(&call("nope", vec![]), &env, &mut int),
+            Err(EvalError::FunctionUndefined(RuntimeError::UndefinedFunc("nope".into())))
+        );
+        assert_eq!(
+            eval_call(&call("xs", vec![]), &env, &mut int),
+            Err(EvalError::FunctionCallError("line 1 col 10".into()))
+        );
+    }
+
+    #[test]
+    fn call_names_failing_argument() {
+        let env = Rc::new(RefCell::new(Environment::default()));
+        env.define("two", Value::Function(Rc::new(Two)));
+        let mut int = Interpreter::default();
+        let call = FnCallExpr {
+            callee: Box::new(Expression::Variable(ident("two"))),
+            paren: Token::new(RIGHT_PAREN, ")".into(), 3, 15),
+            args: vec![number("1"), Box::new(Expression::Variable(ident("undefined")))],
+        };
+        let err = eval_call(&call, &env, &mut int).unwrap_err();
+        let EvalError::FunctionArgError(callee, idx, line, cause) = &err else { panic!("{err:?}") };
+        assert_eq!((callee.as_str(), *idx, *line), ("two", 2, 3));
+        assert!(matches!(**cause, EvalError::VariableEval(RuntimeError::UncaughtReference(..))), "{cause:?}");
+        assert_eq!(err.to_string(), format!("Error evaluating argument 2 of call to two at line 3: {cause}"));
     }
 }
@@ -7,13 +7,23 @@ use crate::parser::value::{Value, ValueResult};
 /// Since a function should always execute in the execution context that was passed to it during its creation, it makes sense
 /// for the caller to not worry about it. For example, a function declared inside a scope should have access to the scope, but it shouldn't
 /// be the caller's responsibility to explicitly mention this detail on every call
-pub trait LoxCallable: std::fmt::Debug {
+/// Its [Display](std::fmt::Display) is how a function value prints
+pub trait LoxCallable: std::fmt::Debug + std::fmt::Display {
     fn call(
         &self,
         args: Vec<Value>,
         interpreter: &mut Interpreter,
     ) -> ValueResult;
+    /// Maximum number of arguments
     fn arity(&self) -> usize;
+    /// Minimum number of arguments, only differs from [arity](LoxCallable::arity) for callables with optional trailing arguments
+    fn min_arity(&self) -> usize {
+        self.arity()
+    }
+    /// Is this callable built into the interpreter rather than declared in Lox source?
+    fn is_native(&self) -> bool {
+        true
+    }
 }
 
 
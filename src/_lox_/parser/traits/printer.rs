@@ -1,4 +1,6 @@
 use super::super::expressions::*;
+use super::visitor::{walk_binary, Visitor};
+use crate::tokenizer::token::Token;
 
 macro_rules! start {
     ($id: tt) => {{
@@ -12,72 +14,127 @@ pub trait ExpressionPrinter {
     fn print(&self) -> String;
 }
 
-impl ExpressionPrinter for Expression {
-    fn print(&self) -> String {
-        match self {
-            Expression::BinExpr(e) => e.print(),
-            Expression::UnExpr(e) => e.print(),
-            Expression::Lit(e) => e.print(),
-            Expression::Group(e) => e.print(),
-            Expression::CommaExpr(e) => e
-                .iter()
-                .map(|expr| expr.print())
-                .collect::<Vec<String>>()
-                .join(" --COMMA EXPR-- "),
-            Expression::TernExpr(e) => {
-                let mut result = format!("Ternary Expression\n");
-                result.push_str(&format!("Condition: {}", &e.condition.print()));
-                result.push_str(&format!("If Condtion true eval: {}", &e.if_true.print()));
-                result.push_str(&format!("If Condtion false eval: {}", &e.if_false.print()));
-                result
-            }
-            Expression::Error(e) => {
-                format!("Printing Erroneous Expression: {}", e.print())
+/// Builds the string for [ExpressionPrinter::print] by visiting the tree
+#[derive(Default)]
+struct AstPrinter {
+    out: String,
+}
+
+impl AstPrinter {
+    fn print(visit: impl FnOnce(&mut Self)) -> String {
+        let mut printer = Self::default();
+        visit(&mut printer);
+        printer.out
+    }
+}
+
+impl Visitor for AstPrinter {
+    fn visit_comma(&mut self, exprs: &[Box<Expression>]) {
+        for (idx, expr) in exprs.iter().enumerate() {
+            if idx > 0 {
+                self.out.push_str(" --COMMA EXPR-- ");
             }
-            Expression::Assignment(AssignmentExpr { name, right }) => {
-                format!("Assignment Expr {name} = {right}")
+            self.visit_expr(expr);
+        }
+    }
+    fn visit_ternary(&mut self, e: &TernaryExpr) {
+        self.out.push_str("Ternary Expression\n");
+        self.out.push_str("Condition: ");
+        self.visit_expr(&e.condition);
+        self.out.push_str("If Condtion true eval: ");
+        self.visit_expr(&e.if_true);
+        self.out.push_str("If Condtion false eval: ");
+        self.visit_expr(&e.if_false);
+    }
+    fn visit_binary(&mut self, e: &BinaryExpr) {
+        self.out.push_str(&start!("BinaryExp"));
+        self.out.push_str(&e.operator.lexeme);
+        walk_binary(self, e);
+    }
+    fn visit_unary(&mut self, e: &UnaryExpr) {
+        self.out.push_str(&start!("UnaryExp"));
+        self.out.push_str(&e.operator.lexeme);
+        self.visit_expr(&e.operand);
+    }
+    fn visit_literal(&mut self, e: &Literal) {
+        self.out.push_str(&start!("Literal"));
+        self.out.push_str(&e.inner.lexeme);
+        self.out.push_str(" )");
+    }
+    fn visit_grouping(&mut self, e: &Grouping) {
+        self.out.push_str(&start!("Grouping"));
+        self.visit_expr(&e.inner);
+        self.out.push_str(" ) ");
+    }
+    fn visit_error(&mut self, e: &Expression) {
+        self.out.push_str("Printing Erroneous Expression: ");
+        self.visit_expr(e);
+    }
+    fn visit_assignment(&mut self, AssignmentExpr { name, right }: &AssignmentExpr) {
+        self.out.push_str(&format!("Assignment Expr {name} = {right}"));
+    }
+    fn visit_variable(&mut self, t: &Token) {
+        self.out.push_str(&format!("Variable {t}"));
+    }
+    fn visit_or(&mut self, l: &OrExpr) {
+        self.out.push_str(&format!("{l}"));
+    }
+    fn visit_and(&mut self, l: &AndExpr) {
+        self.out.push_str(&format!("{l}"));
+    }
+    fn visit_call(&mut self, c: &FnCallExpr) {
+        self.out.push_str(&format!("{c}"));
+    }
+    fn visit_array(&mut self, items: &[Box<Expression>]) {
+        self.out.push_str(&start!("Array"));
+        for (idx, item) in items.iter().enumerate() {
+            if idx > 0 {
+                self.out.push_str(", ");
             }
-            Expression::Variable(t) => format!("Variable {t}"),
-            Expression::LogicOr(l) => format!("{l}"),
-            Expression::LogicAnd(l) => format!("{l}"),
-            Expression::Call(c) => format!("{c}"),
+            self.visit_expr(item);
         }
+        self.out.push_str(" )");
+    }
+    fn visit_get(&mut self, g: &GetExpr) {
+        self.out.push_str(&format!("{g}"));
+    }
+    fn visit_optional_get(&mut self, g: &GetExpr) {
+        self.out.push_str(&format!("{}?.{}", g.object, g.name.lexeme));
+    }
+    fn visit_index(&mut self, i: &IndexExpr) {
+        self.out.push_str(&format!("{i}"));
+    }
+    fn visit_while(&mut self, w: &WhileExpr) {
+        self.out.push_str(&format!("{w}"));
+    }
+}
+
+impl ExpressionPrinter for Expression {
+    fn print(&self) -> String {
+        AstPrinter::print(|printer| printer.visit_expr(self))
     }
 }
 
 impl ExpressionPrinter for Literal {
     fn print(&self) -> String {
-        let mut s = start!("Literal");
-        s.push_str(&self.inner.lexeme);
-        s.push_str(" )");
-        s
+        AstPrinter::print(|printer| printer.visit_literal(self))
     }
 }
 
 impl ExpressionPrinter for Grouping {
     fn print(&self) -> String {
-        let mut s = start!("Grouping");
-        s.push_str(&self.inner.print());
-        s.push_str(" ) ");
-        s
+        AstPrinter::print(|printer| printer.visit_grouping(self))
     }
 }
 
 impl ExpressionPrinter for UnaryExpr {
     fn print(&self) -> String {
-        let mut s = start!("UnaryExp");
-        s.push_str(&self.operator.lexeme);
-        s.push_str(&self.operand.print());
-        s
+        AstPrinter::print(|printer| printer.visit_unary(self))
     }
 }
 
 impl ExpressionPrinter for BinaryExpr {
     fn print(&self) -> String {
-        let mut s = start!("BinaryExp");
-        s.push_str(&self.operator.lexeme);
-        s.push_str(&self.left.print());
-        s.push_str(&self.right.print());
-        s
+        AstPrinter::print(|printer| printer.visit_binary(self))
     }
 }
@@ -0,0 +1,205 @@
+use crate::parser::expressions::*;
+use crate::parser::statement::Stmt;
+use crate::tokenizer::token::Token;
+
+/// A pass over the syntax tree. Every kind of expression has its own `visit_*` method, whose
+/// default walks into the node's children with the matching `walk_*` function. A pass overrides
+/// the methods for the nodes it cares about and calls `walk_*` from them to keep descending.
+///
+/// Statements are all visited through [visit_stmt](Visitor::visit_stmt), whose default
+/// [walk_stmt] visits every statement and expression nested in it
+pub trait Visitor: Sized {
+    fn visit_expr(&mut self, expr: &Expression) {
+        walk_expr(self, expr)
+    }
+    fn visit_stmt(&mut self, stmt: &Stmt) {
+        walk_stmt(self, stmt)
+    }
+    fn visit_comma(&mut self, exprs: &[Box<Expression>]) {
+        walk_list(self, exprs)
+    }
+    fn visit_ternary(&mut self, ternary: &TernaryExpr) {
+        walk_ternary(self, ternary)
+    }
+    fn visit_binary(&mut self, binary: &BinaryExpr) {
+        walk_binary(self, binary)
+    }
+    fn visit_unary(&mut self, unary: &UnaryExpr) {
+        self.visit_expr(&unary.operand)
+    }
+    fn visit_literal(&mut self, _literal: &Literal) {}
+    fn visit_grouping(&mut self, grouping: &Grouping) {
+        self.visit_expr(&grouping.inner)
+    }
+    /// The expression of an error production
+    fn visit_error(&mut self, expr: &Expression) {
+        self.visit_expr(expr)
+    }
+    fn visit_assignment(&mut self, assignment: &AssignmentExpr) {
+        self.visit_expr(&assignment.right)
+    }
+    fn visit_variable(&mut self, _name: &Token) {}
+    fn visit_or(&mut self, or: &OrExpr) {
+        self.visit_expr(&or.left);
+        self.visit_expr(&or.right);
+    }
+    fn visit_and(&mut self, and: &AndExpr) {
+        self.visit_expr(&and.left);
+        self.visit_expr(&and.right);
+    }
+    fn visit_call(&mut self, call: &FnCallExpr) {
+        walk_call(self, call)
+    }
+    fn visit_array(&mut self, items: &[Box<Expression>]) {
+        walk_list(self, items)
+    }
+    fn visit_get(&mut self, get: &GetExpr) {
+        self.visit_expr(&get.object)
+    }
+    fn visit_optional_get(&mut self, get: &GetExpr) {
+        self.visit_expr(&get.object)
+    }
+    fn visit_index(&mut self, index: &IndexExpr) {
+        self.visit_expr(&index.object);
+        self.visit_expr(&index.index);
+    }
+    fn visit_while(&mut self, while_expr: &WhileExpr) {
+        self.visit_expr(&while_expr.condition);
+        self.visit_stmt(&while_expr.body);
+    }
+}
+
+/// Hand `expr` to the visitor method for its kind
+pub fn walk_expr<V: Visitor>(visitor: &mut V, expr: &Expression) {
+    match expr {
+        Expression::CommaExpr(exprs) => visitor.visit_comma(exprs),
+        Expression::TernExpr(ternary) => visitor.visit_ternary(ternary),
+        Expression::BinExpr(binary) => visitor.visit_binary(binary),
+        Expression::UnExpr(unary) => visitor.visit_unary(unary),
+        Expression::Lit(literal) => visitor.visit_literal(literal),
+        Expression::Group(grouping) => visitor.visit_grouping(grouping),
+        Expression::Error(expr) => visitor.visit_error(expr),
+        Expression::Assignment(assignment) => visitor.visit_assignment(assignment),
+        Expression::Variable(name) => visitor.visit_variable(name),
+        Expression::LogicOr(or) => visitor.visit_or(or),
+        Expression::LogicAnd(and) => visitor.visit_and(and),
+        Expression::Call(call) => visitor.visit_call(call),
+        Expression::Array(items) => visitor.visit_array(items),
+        Expression::Get(get) => visitor.visit_get(get),
+        Expression::OptionalGet(get) => visitor.visit_optional_get(get),
+        Expression::Index(index) => visitor.visit_index(index),
+        Expression::While(while_expr) => visitor.visit_while(while_expr),
+    }
+}
+
+/// Visit each expression of a comma expression or an array literal, in order
+pub fn walk_list<V: Visitor>(visitor: &mut V, exprs: &[Box<Expression>]) {
+    exprs.iter().for_each(|expr| visitor.visit_expr(expr))
+}
+
+pub fn walk_ternary<V: Visitor>(visitor: &mut V, ternary: &TernaryExpr) {
+    visitor.visit_expr(&ternary.condition);
+    visitor.visit_expr(&ternary.if_true);
+    visitor.visit_expr(&ternary.if_false);
+}
+
+pub fn walk_binary<V: Visitor>(visitor: &mut V, binary: &BinaryExpr) {
+    visitor.visit_expr(&binary.left);
+    visitor.visit_expr(&binary.right);
+}
+
+/// Visit the callee, then the arguments
+pub fn walk_call<V: Visitor>(visitor: &mut V, call: &FnCallExpr) {
+    visitor.visit_expr(&call.callee);
+    walk_list(visitor, &call.args);
+}
+
+/// Visit the statements and expressions directly nested in `stmt`, in source order
+pub fn walk_stmt<V: Visitor>(visitor: &mut V, stmt: &Stmt) {
+    match stmt {
+        Stmt::FunDecl { defaults, body, .. } => {
+            walk_list(visitor, defaults);
+            visitor.visit_stmt(body);
+        }
+        Stmt::VarDecl { initializer, .. } => {
+            if let Some(initializer) = initializer {
+                visitor.visit_expr(initializer);
+            }
+        }
+        Stmt::ConstDecl { initializer, .. } | Stmt::Destructure { initializer, .. } => {
+            visitor.visit_expr(initializer)
+        }
+        Stmt::ExprStmt(expr) => visitor.visit_expr(expr),
+        Stmt::Print(expr) | Stmt::Break(expr) | Stmt::Return(expr) => {
+            if let Some(expr) = expr {
+                visitor.visit_expr(expr);
+            }
+        }
+        Stmt::ErrStmt { .. } | Stmt::Empty => {}
+        Stmt::Block(stmts) => stmts.iter().for_each(|stmt| visitor.visit_stmt(stmt)),
+        Stmt::IfStmt { condition, then_, else_ } | Stmt::While { condition, body: then_, else_ } => {
+            visitor.visit_expr(condition);
+            visitor.visit_stmt(then_);
+            if let Some(else_) = else_ {
+                visitor.visit_stmt(else_);
+            }
+        }
+        Stmt::ForIn { iterable, body, .. } => {
+            visitor.visit_expr(iterable);
+            visitor.visit_stmt(body);
+        }
+        Stmt::Repeat { count, body } => {
+            visitor.visit_expr(count);
+            visitor.visit_stmt(body);
+        }
+        Stmt::Located { stmt, .. } => visitor.visit_stmt(stmt),
+        Stmt::TryCatch { try_block, catch_block, .. } => {
+            visitor.visit_stmt(try_block);
+            visitor.visit_stmt(catch_block);
+        }
+        Stmt::Switch { discriminant, cases, default } => {
+            visitor.visit_expr(discriminant);
+            for (value, body) in cases {
+                visitor.visit_expr(value);
+                body.iter().for_each(|stmt| visitor.visit_stmt(stmt));
+            }
+            default.iter().flatten().for_each(|stmt| visitor.visit_stmt(stmt));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::tokenizer::scanner::Scanner;
+    use crate::Lox;
+
+    /// Counts the binary expressions in a tree
+    #[derive(Default)]
+    struct BinaryCounter(usize);
+
+    impl Visitor for BinaryCounter {
+        fn visit_binary(&mut self, binary: &BinaryExpr) {
+            self.0 += 1;
+            walk_binary(self, binary);
+        }
+    }
+
+    fn count_binaries(src: &str) -> usize {
+        let mut lox = Lox::new(src.into());
+        let mut scanner = Scanner::new(src, &mut lox);
+        scanner.scan_tokens();
+        let mut counter = BinaryCounter::default();
+        Parser::new(scanner.tokens).parse().iter().for_each(|stmt| counter.visit_stmt(stmt));
+        counter.0
+    }
+
+    #[test]
+    fn counts_binary_expressions() {
+        assert_eq!(count_binaries("print 1;"), 0);
+        assert_eq!(count_binaries("print -(1 + 2) * 3;"), 2);
+        assert_eq!(count_binaries("var a = f(1 - 2, [3 / 4]); if (a > 1) { print a == 2 ? 1 : 2; }"), 4);
+        assert_eq!(count_binaries("fun f(x, y = 1 + 1) { while (x < 3) x = x + 1; }"), 3);
+    }
+}
@@ -1,7 +1,9 @@
-use std::{borrow::Cow, cell::RefCell, rc::Rc};
+use std::{borrow::Cow, cell::RefCell, collections::HashMap, rc::Rc};
 
 use super::error::EvalError;
+use super::expressions::Expression;
 use super::statement::Stmt;
+use super::traits::evaluate::Evaluate;
 use crate::interpreter::{Environment, Interpreter, Memory};
 use crate::parser::traits::lox_callable::LoxCallable;
 use crate::tokenizer::token::Token;
@@ -15,10 +17,22 @@ pub enum Value {
     /// Think of () as a postfix operator, then the justification for including a Function in Lox value makes sense
     /// `LoxVal()`.eval() -> Another `LoxVal2` which may be another function and evaluated as `LoxVal2`.eval() -> LoxVal3
     Function(Rc<dyn LoxCallable>),
-    Break,
+    /// Signals a `break` out of the innermost loop, with the value it broke with
+    Break(Box<Value>),
+    /// Signals a `return` out of the innermost function call, with the value it returned
+    Return(Box<Value>),
     Double(f64),
     Bool(bool),
-    String(String),
+    /// Strings are immutable, so values share them and a clone only bumps a reference count
+    String(Rc<str>),
+    /// A single unicode scalar value, written as `'x'`
+    Char(char),
+    /// Arrays are shared by reference, so mutating one through any binding is seen by all of them
+    Array(Rc<RefCell<Vec<Value>>>),
+    /// String keyed maps, made by `map()`. Shared by reference like arrays
+    Map(Rc<RefCell<HashMap<HashKey, Value>>>),
+    /// Raw bytes, made by `hex_decode`
+    Bytes(Vec<u8>),
     #[default]
     Nil,
 }
@@ -35,9 +49,32 @@ impl Value {
             _ => None,
         }
     }
+    /// The callable behind this value if it can be called with `()`
+    pub fn as_callable(&self) -> Option<Rc<dyn LoxCallable>> {
+        match self {
+            Value::Function(f) => Some(Rc::clone(f)),
+            _ => None,
+        }
+    }
     pub fn is_equal(&self, other: &Value) -> bool {
         self == other
     }
+    /// Name of the value's type as shown to the user in error messages
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Function(_) => "function",
+            Value::Break(_) => "break",
+            Value::Return(_) => "return",
+            Value::Double(_) => "number",
+            Value::Bool(_) => "bool",
+            Value::String(_) => "string",
+            Value::Char(_) => "char",
+            Value::Array(_) => "array",
+            Value::Map(_) => "map",
+            Value::Bytes(_) => "bytes",
+            Value::Nil => "nil",
+        }
+    }
     /// Only false, and nil are falsey, rest everything else is truthy
     pub fn is_truthy(&self) -> bool {
         match self {
@@ -46,52 +83,173 @@ impl Value {
             _ => true,
         }
     }
+    /// Truthiness in a condition run by `interpreter`, which also treats the values
+    /// marked with the `set_falsy` native as false
+    pub fn is_truthy_in(&self, interpreter: &Interpreter) -> bool {
+        self.is_truthy() && !interpreter.falsy.contains(self)
+    }
 }
 impl PartialEq for Value {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
-            (Self::Function(l0), Self::Function(r0)) => {
-                let l = &*l0 as &dyn std::any::Any;
-                let r = &*r0 as &dyn std::any::Any;
-                match (
-                    l.downcast_ref::<LoxFunction>(),
-                    r.downcast_ref::<LoxFunction>(),
-                ) {
-                    (Some(l1), Some(l2)) => l1 == l2,
-                    (_, _) => false,
-                }
-            }
+            // A function is only equal to itself
+            (Self::Function(l0), Self::Function(r0)) => Rc::ptr_eq(l0, r0),
             (Self::Double(l0), Self::Double(r0)) => l0 == r0,
             (Self::Bool(l0), Self::Bool(r0)) => l0 == r0,
             (Self::String(l0), Self::String(r0)) => l0 == r0,
+            (Self::Char(l0), Self::Char(r0)) => l0 == r0,
+            (Self::Array(l0), Self::Array(r0)) => *l0.borrow() == *r0.borrow(),
+            (Self::Map(l0), Self::Map(r0)) => *l0.borrow() == *r0.borrow(),
+            (Self::Bytes(l0), Self::Bytes(r0)) => l0 == r0,
             _ => core::mem::discriminant(self) == core::mem::discriminant(other),
         }
     }
 }
 impl std::cmp::PartialOrd for Value {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        use std::cmp::Ordering::*;
         match (self.is_numeric(), other.is_numeric()) {
             (Some(l), Some(r)) => l.partial_cmp(&r),
             _ => match (&self, &other) {
                 (Value::Bool(l), Value::Bool(r)) => l.partial_cmp(&r),
                 (Value::String(l), Value::String(r)) => l.partial_cmp(&r),
-                (Value::Nil, Value::Bool(_)) => None, // We disallow nil to be compared against booleans, may change if needed
-                (Value::Nil, Value::Nil) => Some(Equal),
+                // chars compare by code point
+                (Value::Char(l), Value::Char(r)) => l.partial_cmp(&r),
+                // nil, functions, arrays, maps and bytes have no order, `==` compares them with [Value::lox_eq]
                 _ => None,
             },
         }
     }
 }
+impl Value {
+    /// What `==` evaluates to, `None` when the operands can't be compared. Values without an order
+    /// are still equal to values of their own type that are [PartialEq]
+    pub fn lox_eq(&self, other: &Self) -> Option<bool> {
+        match self.partial_cmp(other) {
+            Some(ordering) => Some(ordering == std::cmp::Ordering::Equal),
+            None if core::mem::discriminant(self) == core::mem::discriminant(other) => Some(self == other),
+            None => None,
+        }
+    }
+}
 impl std::fmt::Display for Value {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
+            Value::Double(x) if x.is_nan() => write!(f, "NaN"),
+            Value::Double(x) if x.is_infinite() => {
+                write!(f, "{}", if *x > 0.0 { "Infinity" } else { "-Infinity" })
+            }
             Value::Double(x) => write!(f, "{x}"),
             Value::Bool(x) => write!(f, "{x}"),
             Value::String(x) => write!(f, "\"{x}\""),
+            // Escaped the way a char literal is written, anything else prints as it is
+            Value::Char(x) => match x {
+                '\n' => write!(f, "'\\n'"),
+                '\t' => write!(f, "'\\t'"),
+                '\r' => write!(f, "'\\r'"),
+                '\0' => write!(f, "'\\0'"),
+                '\\' => write!(f, "'\\\\'"),
+                '\'' => write!(f, "'\\''"),
+                x => write!(f, "'{x}'"),
+            },
+            Value::Array(items) => {
+                let items = items.borrow();
+                let items: Vec<String> = items.iter().map(|item| item.to_string()).collect();
+                write!(f, "[{}]", items.join(", "))
+            }
+            Value::Map(entries) => {
+                // Sorted so that printing a map is deterministic
+                let mut entries: Vec<String> = entries
+                    .borrow()
+                    .iter()
+                    .map(|(key, value)| format!("{}: {value}", Value::from(key.clone())))
+                    .collect();
+                entries.sort();
+                write!(f, "{{{}}}", entries.join(", "))
+            }
+            Value::Bytes(bytes) => write!(f, "<bytes len={}>", bytes.len()),
             Value::Nil => write!(f, "Nil"),
-            Value::Break => write!(f, "BreakValue"),
-            Value::Function(_) => todo!(),
+            Value::Break(_) => write!(f, "BreakValue"),
+            Value::Return(_) => write!(f, "ReturnValue"),
+            Value::Function(callable) => write!(f, "{callable}"),
+        }
+    }
+}
+/// Canonical form of a [Value] that can key a map. Built by [Value::try_hash_key]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum HashKey {
+    /// Bits of the number, with `-0.0` folded into `0.0` so that equal numbers get equal keys
+    Number(u64),
+    Bool(bool),
+    String(Rc<str>),
+    Char(char),
+}
+impl Value {
+    /// Key for this value in a map. Only numbers, bools, strings and chars can be keys,
+    /// NaN can't either since it isn't equal to itself
+    pub fn try_hash_key(&self) -> Result<HashKey, EvalError> {
+        match self {
+            Value::Double(n) if n.is_nan() => Err(EvalError::UnhashableKey("NaN")),
+            Value::Double(n) => Ok(HashKey::Number(if *n == 0.0 { 0f64.to_bits() } else { n.to_bits() })),
+            Value::Bool(b) => Ok(HashKey::Bool(*b)),
+            Value::String(s) => Ok(HashKey::String(s.clone())),
+            Value::Char(c) => Ok(HashKey::Char(*c)),
+            other => Err(EvalError::UnhashableKey(other.type_name())),
+        }
+    }
+}
+impl From<HashKey> for Value {
+    fn from(key: HashKey) -> Self {
+        match key {
+            HashKey::Number(bits) => Value::Double(f64::from_bits(bits)),
+            HashKey::Bool(b) => Value::Bool(b),
+            HashKey::String(s) => Value::String(s),
+            HashKey::Char(c) => Value::Char(c),
+        }
+    }
+}
+/// How `Value::Double`s are displayed when printed by the interpreter
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum NumberFormat {
+    /// Shortest representation that round-trips
+    #[default]
+    Shortest,
+    /// Fixed number of decimal places
+    Fixed(usize),
+    /// Scientific notation, optionally with a fixed number of decimal places
+    Scientific(Option<usize>),
+}
+/// Values that conditions treat as false besides `false` and `nil`, set by the `set_falsy` native
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Falsy {
+    /// `""`
+    pub empty_string: bool,
+    /// `0` and `-0`
+    pub zero: bool,
+    /// `[]`
+    pub empty_array: bool,
+}
+impl Falsy {
+    /// Is `value` one of the extra falsy values?
+    pub fn contains(&self, value: &Value) -> bool {
+        match value {
+            Value::String(s) => self.empty_string && s.is_empty(),
+            Value::Double(n) => self.zero && *n == 0.0,
+            Value::Array(items) => self.empty_array && items.borrow().is_empty(),
+            _ => false,
+        }
+    }
+}
+impl Value {
+    /// Display the value, formatting numbers according to `format`
+    pub fn format_with(&self, format: &NumberFormat) -> String {
+        match (self, format) {
+            (Value::Double(x), _) if !x.is_finite() => self.to_string(),
+            (Value::Double(x), NumberFormat::Fixed(precision)) => format!("{x:.precision$}"),
+            (Value::Double(x), NumberFormat::Scientific(None)) => format!("{x:e}"),
+            (Value::Double(x), NumberFormat::Scientific(Some(precision))) => {
+                format!("{x:.precision$e}")
+            }
+            _ => self.to_string(),
         }
     }
 }
@@ -102,7 +260,17 @@ impl From<bool> for Value {
 }
 impl From<String> for Value {
     fn from(x: String) -> Self {
-        Self::String(x)
+        Self::String(x.into())
+    }
+}
+impl From<char> for Value {
+    fn from(c: char) -> Self {
+        Self::Char(c)
+    }
+}
+impl From<Vec<Value>> for Value {
+    fn from(items: Vec<Value>) -> Self {
+        Self::Array(Rc::new(RefCell::new(items)))
     }
 }
 impl From<f64> for Value {
@@ -110,8 +278,27 @@ impl From<f64> for Value {
         Self::Double(f)
     }
 }
+/// For natives: `let n: f64 = (&args[0]).try_into()?;`
+impl TryFrom<&Value> for f64 {
+    type Error = EvalError;
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        value
+            .is_numeric()
+            .ok_or_else(|| EvalError::InvalidArgument(format!("expected a number, got {}", value.type_name())))
+    }
+}
+/// For natives: `let s: String = (&args[0]).try_into()?;`
+impl TryFrom<&Value> for String {
+    type Error = EvalError;
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        value
+            .is_string()
+            .map(Cow::into_owned)
+            .ok_or_else(|| EvalError::InvalidArgument(format!("expected a string, got {}", value.type_name())))
+    }
+}
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Clone, PartialEq)]
 pub struct LoxFunction {
     /// Environment in which to execute function body
     pub stack_env: Rc<RefCell<Environment>>,
@@ -123,22 +310,207 @@ pub struct LoxFunction {
     /// We need this as HashMaps don't preserve order in keys and we need
     /// order to figure out correct instantiation of variables
     pub params : Vec<String>,
+    /// Defaults of the trailing parameters, evaluated in the function's scope when an argument is left out
+    pub defaults: Vec<Box<Expression>>,
+}
+
+/// Leaves out `stack_env`, which holds the function itself once it's declared
+impl std::fmt::Debug for LoxFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LoxFunction")
+            .field("ident", &self.ident.lexeme)
+            .field("params", &self.params)
+            .finish_non_exhaustive()
+    }
+}
+
+impl std::fmt::Display for LoxFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<fn {}>", self.ident.lexeme)
+    }
 }
 
 /// Since LoxFunction is a special value that can be called, we express that through this trait
 impl LoxCallable for LoxFunction {
     fn call(&self, args: Vec<Value>, interpreter: &mut Interpreter) -> ValueResult {
         // println!("FUnction BoDy -> {}", self.body);
-        if args.len() != self.params.len() {
+        if args.is_empty() && self.min_arity() > 0 || args.len() > self.params.len() {
             return Err(EvalError::ArityMismatch(self.params.len(), args.len()));
         }
-        // let stack_env = self.stack_env.borrow_mut();
+        if args.len() < self.min_arity() {
+            return Ok(Value::Function(Rc::new(PartialFunction { function: self.clone(), args })));
+        }
+        // Every call binds its arguments in a scope of its own, so a recursive call can't overwrite
+        // the parameters of the call that made it
+        let call_env = Rc::new(RefCell::new(Environment::enclosed_by(Rc::clone(&self.stack_env))));
+        let passed = args.len();
         for (name, value) in self.params.iter().zip(args.into_iter()) {
-            self.stack_env.put(name, value).expect("ICE: unhandled function argument intialization error");
+            call_env.define(name, value);
+        }
+        // Left out arguments take their defaults, which can refer to the parameters before them
+        let first_default = self.params.len() - self.defaults.len();
+        for (idx, name) in self.params.iter().enumerate().skip(passed) {
+            let value = self.defaults[idx - first_default].eval(&call_env, interpreter)?;
+            call_env.define(name, value);
+        }
+        interpreter.call_depth += 1;
+        let result = interpreter.execute(&self.body, call_env, false);
+        interpreter.call_depth -= 1;
+        match result? {
+            Value::Return(value) => Ok(*value),
+            // Ran off the end of the body
+            _ => Ok(Value::Nil),
         }
-        interpreter.execute(&self.body, Rc::clone(&self.stack_env), false)
     }
     fn arity(&self) -> usize {
         self.arity
     }
+    fn min_arity(&self) -> usize {
+        self.arity - self.defaults.len()
+    }
+    fn is_native(&self) -> bool {
+        false
+    }
+}
+
+/// What calling a Lox function with some but not all of its required arguments gives back.
+/// Calling it with the rest runs the function, `add(1)(4)` is `add(1, 4)`
+#[derive(Debug)]
+pub struct PartialFunction {
+    function: LoxFunction,
+    /// The arguments given so far
+    args: Vec<Value>,
+}
+
+/// Prints as the function it applies
+impl std::fmt::Display for PartialFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.function.fmt(f)
+    }
+}
+
+impl LoxCallable for PartialFunction {
+    fn call(&self, args: Vec<Value>, interpreter: &mut Interpreter) -> ValueResult {
+        let mut all = self.args.clone();
+        all.extend(args);
+        self.function.call(all, interpreter)
+    }
+    fn arity(&self) -> usize {
+        self.function.arity() - self.args.len()
+    }
+    fn min_arity(&self) -> usize {
+        self.function.min_arity() - self.args.len()
+    }
+    fn is_native(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+
+    fn hash_of(value: Value) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        value.try_hash_key().unwrap().hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn hash_keys_are_consistent() {
+        assert_eq!(hash_of(Value::Double(1.5)), hash_of(Value::Double(1.5)));
+        assert_eq!(hash_of(Value::Double(0.0)), hash_of(Value::Double(-0.0)));
+        assert_eq!(hash_of("key".to_string().into()), hash_of("key".to_string().into()));
+        assert_ne!(
+            Value::Double(1.0).try_hash_key(),
+            Value::String("1".into()).try_hash_key()
+        );
+        assert_eq!(Value::from(Value::Double(2.0).try_hash_key().unwrap()), Value::Double(2.0));
+    }
+
+    #[test]
+    fn unhashable_keys() {
+        #[derive(Debug, derive_more::Display)]
+        #[display(fmt = "<native fn: noop>")]
+        struct Noop;
+        impl LoxCallable for Noop {
+            fn call(&self, _: Vec<Value>, _: &mut Interpreter) -> ValueResult {
+                Ok(Value::Nil)
+            }
+            fn arity(&self) -> usize {
+                0
+            }
+        }
+        let function = Value::Function(Rc::new(Noop));
+        assert_eq!(function.try_hash_key(), Err(EvalError::UnhashableKey("function")));
+        assert_eq!(Value::Nil.try_hash_key(), Err(EvalError::UnhashableKey("nil")));
+        assert_eq!(Value::from(vec![]).try_hash_key(), Err(EvalError::UnhashableKey("array")));
+        assert_eq!(Value::Double(f64::NAN).try_hash_key(), Err(EvalError::UnhashableKey("NaN")));
+    }
+
+    #[test]
+    fn non_finite_numbers_display() {
+        assert_eq!(Value::Double(f64::INFINITY).to_string(), "Infinity");
+        assert_eq!(Value::Double(f64::NEG_INFINITY).to_string(), "-Infinity");
+        assert_eq!(Value::Double(f64::NAN).to_string(), "NaN");
+        assert_eq!(Value::Double(f64::INFINITY).format_with(&NumberFormat::Fixed(2)), "Infinity");
+        assert_eq!(Value::Double(1.5).to_string(), "1.5");
+    }
+
+    #[test]
+    fn chars_display() {
+        assert_eq!(Value::Char('a').to_string(), "'a'");
+        assert_eq!(Value::Char('é').to_string(), "'é'");
+        assert_eq!(Value::Char('\n').to_string(), "'\\n'");
+        assert_eq!(Value::Char('\'').to_string(), "'\\''");
+        assert_eq!(Value::Char('\\').to_string(), "'\\\\'");
+    }
+
+    #[test]
+    fn callables() {
+        #[derive(Debug, derive_more::Display)]
+        #[display(fmt = "<native fn: two>")]
+        struct Two;
+        impl LoxCallable for Two {
+            fn call(&self, _: Vec<Value>, _: &mut Interpreter) -> ValueResult {
+                Ok(Value::Double(2.0))
+            }
+            fn arity(&self) -> usize {
+                0
+            }
+        }
+        let callable = Value::Function(Rc::new(Two)).as_callable().expect("functions are callable");
+        assert_eq!(callable.call(vec![], &mut Interpreter::default()), Ok(Value::Double(2.0)));
+        assert!(Value::Double(1.0).as_callable().is_none());
+        assert!(Value::Nil.as_callable().is_none());
+    }
+
+    #[test]
+    fn try_into_primitives() {
+        let n: Result<f64, _> = (&Value::Double(1.5)).try_into();
+        assert_eq!(n, Ok(1.5));
+        let n: Result<f64, _> = (&Value::Nil).try_into();
+        assert_eq!(n, Err(EvalError::InvalidArgument("expected a number, got nil".into())));
+        let s: Result<String, _> = (&Value::String("lox".into())).try_into();
+        assert_eq!(s, Ok("lox".to_string()));
+        let s: Result<String, _> = (&Value::Double(1.0)).try_into();
+        assert!(s.is_err());
+    }
+
+    #[test]
+    fn strings_are_shared_on_clone() {
+        let s = Value::from("lox".to_string());
+        let copy = s.clone();
+        match (&s, &copy) {
+            (Value::String(a), Value::String(b)) => assert!(Rc::ptr_eq(a, b)),
+            _ => unreachable!(),
+        }
+        assert_eq!(s, copy);
+        assert_eq!(s.partial_cmp(&Value::String("lux".into())), Some(std::cmp::Ordering::Less));
+        let mut lox = crate::Lox::new(String::new());
+        assert_eq!(lox.eval_capture(r#""lo" + "x""#).0, Some(s));
+        assert_eq!(lox.eval_capture(r#""lo" + "x" == "lox""#).0, Some(Value::Bool(true)));
+    }
 }
@@ -39,6 +39,45 @@ and or not_a_keyword
         dbg!(tokens);
     }
 
+    #[test]
+    fn is_keyword() {
+        let source = "var x = while_ 1 class default print \"if\" true";
+        let mut lox = Lox::new(source.into());
+        let mut scanner = Scanner::new(source, &mut lox);
+        scanner.scan_tokens();
+        let keywords: Vec<bool> = scanner.tokens.iter().map(|t| t.is_keyword()).collect();
+        // var x = while_ 1 class default print "if" true EOF
+        assert_eq!(keywords, [true, false, false, false, false, true, true, true, false, true, false]);
+    }
+
+    #[test]
+    fn max_tokens() {
+        let source = "1 + 2 + 3 + 4;";
+        let mut lox = Lox::new(source.into());
+        let mut scanner = Scanner::new(source, &mut lox);
+        scanner.set_max_tokens(Some(4));
+        scanner.scan_tokens();
+        // the limit, then EOF
+        assert_eq!(scanner.tokens.len(), 5);
+        assert!(lox.had_error);
+        assert_eq!(lox.diagnostics[0].message, "Too many tokens, scanning stopped after 4");
+    }
+
+    #[test]
+    fn line_continuation() {
+        let source = "print 1 +\\\n  2 \\\r\n+ 3;";
+        let mut lox = Lox::new(source.into());
+        lox.newline_terminators = true;
+        let mut scanner = Scanner::new(source, &mut lox);
+        scanner.scan_tokens();
+        use crate::tokenizer::token_type::TokenType::{self, *};
+        let types: Vec<TokenType> = scanner.tokens.iter().map(|t| t.r#type).collect();
+        assert_eq!(types, [PRINT, NUMBER, PLUS, NUMBER, PLUS, NUMBER, SEMICOLON, EOF]);
+        // physical lines are still counted
+        assert_eq!(scanner.tokens[5].ln, 3);
+        assert!(!lox.had_error);
+    }
+
     #[test]
     fn bad_number1() {
         let source = String::from("..123");
@@ -108,6 +147,104 @@ adsadasdasdasd
         dbg!(tokens);
         assert_eq!(scanner.current, source.len());
     }
+
+    fn scan(source: &str) -> (Vec<crate::tokenizer::token::Token>, bool) {
+        let mut lox = Lox::new(source.into());
+        let mut scanner = Scanner::new(source, &mut lox);
+        scanner.scan_tokens();
+        let tokens = scanner.tokens;
+        (tokens, lox.had_error)
+    }
+
+    #[test]
+    fn commas_and_square_brackets() {
+        use crate::tokenizer::token_type::TokenType::{self, *};
+        let types = |source: &str| scan(source).0.iter().map(|t| t.r#type).collect::<Vec<TokenType>>();
+        assert_eq!(types("1,2,3"), [NUMBER, COMMA, NUMBER, COMMA, NUMBER, EOF]);
+        assert_eq!(types("[1, 2]"), [LEFT_SQUARE, NUMBER, COMMA, NUMBER, RIGHT_SQUARE, EOF]);
+    }
+
+    #[test]
+    fn dot_for_property_access() {
+        use crate::tokenizer::token_type::TokenType::{self, *};
+        let types = |source: &str| scan(source).0.iter().map(|t| t.r#type).collect::<Vec<TokenType>>();
+        assert_eq!(types("a.b"), [IDENTIFIER, DOT, IDENTIFIER, EOF]);
+        assert_eq!(types("f().x"), [IDENTIFIER, LEFT_PAREN, RIGHT_PAREN, DOT, IDENTIFIER, EOF]);
+        assert_eq!(types("a?.b"), [IDENTIFIER, QUESTION_DOT, IDENTIFIER, EOF]);
+        // decimal points stay part of the number
+        let (tokens, had_error) = scan("1.5");
+        assert!(!had_error);
+        assert_eq!((tokens[0].r#type, tokens[0].lexeme.as_str()), (NUMBER, "1.5"));
+    }
+
+    #[test]
+    fn number_separators() {
+        use crate::tokenizer::token_type::TokenType;
+        for source in ["1_000_000", "3.141_592", "1_0.5_5"] {
+            let (tokens, had_error) = scan(source);
+            assert!(!had_error, "{source}");
+            assert_eq!(tokens[0].r#type, TokenType::NUMBER);
+            assert_eq!(tokens[0].lexeme, source);
+        }
+        for source in ["1__0", "1_", "1_.5", "1._5", "1_000_"] {
+            assert!(scan(source).1, "{source}");
+        }
+        // a leading underscore makes an identifier
+        assert_eq!(scan("_1").0[0].r#type, TokenType::IDENTIFIER);
+    }
+
+    #[test]
+    fn char_literal() {
+        use crate::tokenizer::token_type::TokenType;
+        let (tokens, had_error) = scan("'a'");
+        assert!(!had_error);
+        assert_eq!(tokens[0].r#type, TokenType::CHAR);
+        assert_eq!(tokens[0].lexeme, "a");
+        assert_eq!(tokens[1].r#type, TokenType::EOF);
+
+        let (tokens, had_error) = scan(r"'\n'");
+        assert!(!had_error);
+        assert_eq!(tokens[0].r#type, TokenType::CHAR);
+        assert_eq!(tokens[0].lexeme, "\n");
+    }
+
+    #[test]
+    fn string_escapes() {
+        use crate::tokenizer::token_type::TokenType;
+        for (source, cooked) in [
+            (r#""line\nbreak""#, "line\nbreak"),
+            (r#""a\tb""#, "a\tb"),
+            (r#""a\rb""#, "a\rb"),
+            (r#""a\\b""#, "a\\b"),
+            (r#""say \"hi\"""#, "say \"hi\""),
+            (r#""a\0b""#, "a\0b"),
+        ] {
+            let (tokens, had_error) = scan(source);
+            assert!(!had_error, "{source}");
+            assert_eq!(tokens[0].r#type, TokenType::STRING);
+            assert_eq!(tokens[0].lexeme, cooked);
+            assert_eq!(tokens[1].r#type, TokenType::EOF);
+        }
+        assert!(scan(r#""bad \q escape""#).1);
+        // an escaped quote doesn't close the string
+        assert!(scan(r#""open \""#).1);
+    }
+
+    #[test]
+    fn leading_bom_is_skipped() {
+        let source = "var a = \"héllo\";\nprint a;";
+        let (with_bom, had_error) = scan(&format!("\u{FEFF}{source}"));
+        assert!(!had_error);
+        assert_eq!(with_bom, scan(source).0);
+    }
+
+    #[test]
+    fn multi_char_literal_is_an_error() {
+        let (tokens, had_error) = scan("'ab' 1");
+        assert!(had_error);
+        // Scanning resumes after the bad literal
+        assert_eq!(tokens[0].lexeme, "1");
+    }
 }
 
 mod parser_tests {
@@ -152,6 +289,49 @@ mod parser_tests {
         assert!(parser_result.is_ok());
         println!("Source : \"1+(2.3+3.4)*(4*5)\"\nParser Result : {parser_result:?}")
     }
+    #[test]
+    fn chained_postfix_expressions() {
+        use crate::parser::expressions::*;
+        let tokens = setup_lox!("a.b().c[0].d");
+        let a_b = Expression::Get(GetExpr {
+            object: Box::new(Expression::Variable(tokens[0].clone())),
+            name: tokens[2].clone(),
+        });
+        let call = Expression::Call(FnCallExpr { callee: Box::new(a_b), paren: tokens[4].clone(), args: vec![] });
+        let c = Expression::Get(GetExpr { object: Box::new(call), name: tokens[6].clone() });
+        let index = Expression::Index(IndexExpr {
+            object: Box::new(c),
+            index: Box::new(Expression::Lit(Literal::new(tokens[8].clone()).unwrap())),
+            bracket: tokens[9].clone(),
+        });
+        let expected = Expression::Get(GetExpr { object: Box::new(index), name: tokens[11].clone() });
+        assert_eq!(Parser::new(tokens).run(), Ok(Box::new(expected)));
+    }
+
+    #[test]
+    fn index_arrays() {
+        let eval = |src: &str| {
+            let mut lox = Lox::new(src.into());
+            let mut scanner = Scanner::new(src, &mut lox);
+            scanner.scan_tokens();
+            let tokens = scanner.tokens;
+            let env = std::rc::Rc::new(std::cell::RefCell::new(interpreter::Environment::default()));
+            Parser::new(tokens).run().unwrap().eval(&env, &mut Interpreter::default())
+        };
+        assert_eq!(eval("[10, 20, 30][1]"), Ok(Value::Double(20.0)));
+        assert_eq!(eval("[[1], [2, 3]][1][0]"), Ok(Value::Double(2.0)));
+        assert_eq!(eval("[10, 20][1.0]"), Ok(Value::Double(20.0)));
+        assert_eq!(
+            eval("[10, 20][1.5]"),
+            Err(crate::parser::error::EvalError::InvalidArgument("array index must be a whole number, found 1.5".into()))
+        );
+        assert_eq!(
+            eval("[10][1]"),
+            Err(crate::parser::error::EvalError::IndexOutOfBounds(1.0, 1))
+        );
+        assert_eq!(eval("1[0]"), Err(crate::parser::error::EvalError::NotIndexable("number")));
+    }
+
     #[test]
     fn illegal_termination() {
         let tokens = setup_lox!("1+3+4/");
@@ -223,26 +403,23 @@ mod parser_tests {
     #[test]
     fn check_nested_ternary_expression3() {
         // let tokens = setup_lox!("var a; var b; var c; var d; var e; a = !(b = 2) ? c = 2 : d = !(e = 3) ? 100 : 1000;");
-        let mut env = Rc::new(RefCell::new(Environment::default()));
-        {
-            let mut e = env.borrow_mut();
-            e.values.insert("a".to_string(), Value::Nil);
-            e.values.insert("b".to_string(), Value::Nil);
-            e.values.insert("c".to_string(), Value::Nil);
-            e.values.insert("d".to_string(), Value::Nil);
-            e.values.insert("e".to_string(), Value::Nil);
-        } // RefMut dropped here
+        let names = ["a", "b", "c", "d", "e"];
+        let env = Rc::new(RefCell::new(Environment::preloaded(names.map(|name| (name.to_string(), Value::Nil)))));
         let tokens = setup_lox!("a = !(b = 2) ? c = 2 : d = !(e = 3) ? 100 : 1000;");
-        let p = Parser::new(tokens);
+        let stmts = Parser::new(tokens).parse();
         let mut int = Interpreter::default();
-        &mut int.extend_with_env(p, env);
-        // figure out a way to test from stdout
-        println!("var a = !(b = 2) ? c = 2 : d = !(e = 3) ? 100 : 1000; -> \n");
-        int.interpret();
-        // assert_eq!(res, Value::Double(1000.0));
+        assert_eq!(int.extend_with_env(stmts, Rc::clone(&env)), Ok(()));
+        // The statements ran against `env`, not the interpreter's globals
+        let value = |name: &str| env.borrow().values.get(name).cloned();
+        assert_eq!(value("a"), Some(Value::Double(1000.0)));
+        assert_eq!(value("b"), Some(Value::Double(2.0)));
+        assert_eq!(value("c"), Some(Value::Nil));
+        assert_eq!(value("d"), Some(Value::Double(1000.0)));
+        assert_eq!(value("e"), Some(Value::Double(3.0)));
     }
     #[test]
-    /// Missing left operand. This should trigger a synchronization and pick up parsing from 10+11==12
+    /// Missing left operand. The parser recovers from the stray operators but the result is
+    /// an error, not a tree, as the parse was corrupt
     fn incomplete_expressions() {
         // let tokens = setup_lox!("1+");
         // let tokens = setup_lox!("-+*4/62;10+11==12"); // works
@@ -264,9 +441,23 @@ mod parser_tests {
             let res = Parser::new(case.clone()).run();
             // println!("Input : {case:?} ");
             println!("Result : {res:#?}");
-            assert!(res.is_ok());
+            assert!(matches!(res, Err(ParserError::ErrorProduction(_))));
         }
     }
+
+    #[test]
+    fn corrupt_parse_is_an_error() {
+        let res = Parser::new(setup_lox!("1 2")).run();
+        assert!(matches!(res, Err(ParserError::InvalidToken(Some(_)))), "{res:?}");
+        let res = Parser::new(setup_lox!("1 (2)")).run();
+        assert!(matches!(res, Err(ParserError::ErrorProduction(_))), "{res:?}");
+        // statements too are not run if they parsed corrupt
+        let mut parser = Parser::new(setup_lox!("print 1 (2); print 3;"));
+        let stmts = parser.parse();
+        use crate::parser::statement::Stmt;
+        assert!(matches!(stmts[0], Stmt::ErrStmt { .. }));
+        assert!(matches!(stmts[1].inner(), Stmt::Print(Some(_))));
+    }
     #[test]
     /// Missing left operand. This should trigger a synchronization and pick up parsing from 10+11==12
     fn incomplete_expressions_special1() {
@@ -326,7 +517,193 @@ mod parser_tests {
 mod parser_evaluator {
 
     use super::*;
+    use crate::interpreter::Interpreter;
+    use crate::interpreter::Memory;
+    use crate::parser::error::{EvalError, RuntimeError};
+    use crate::parser::value::ValueResult;
     use crate::{parser::traits::evaluate::Evaluate, setup_lox};
+
+    /// Scan, parse and evaluate `src` as a single expression
+    fn eval(src: &str) -> ValueResult {
+        eval_in(src, &Rc::new(RefCell::new(Environment::default())), &mut Interpreter::default())
+    }
+
+    /// Like [eval], in the given environment and interpreter
+    fn eval_in(src: &str, env: &Rc<RefCell<Environment>>, interpreter: &mut Interpreter) -> ValueResult {
+        let mut lox = Lox::new(src.into());
+        let mut scanner = Scanner::new(src, &mut lox);
+        scanner.scan_tokens();
+        Parser::new(scanner.tokens)
+            .run()
+            .expect("test source should parse")
+            .eval(env, interpreter)
+    }
+
+    #[test]
+    fn preloaded_environment() {
+        let env = Rc::new(RefCell::new(Environment::preloaded(vec![
+            ("a".to_string(), Value::Double(1.0)),
+            ("b".to_string(), Value::Double(2.0)),
+            ("s".to_string(), Value::String("x".into())),
+        ])));
+        let tokens = setup_lox!("s + (a + b)");
+        let res = Parser::new(tokens).run().unwrap().eval(&env, &mut Interpreter::default());
+        assert_eq!(res, Ok(Value::String("x3".into())));
+        assert_eq!(env.borrow().depth(), 1);
+    }
+
+    #[test]
+    fn concatenate_bools_and_nil() {
+        let eval_with = |src: &str, strict: bool| {
+            let mut interpreter = Interpreter::default();
+            interpreter.set_strict_concat(strict);
+            eval_in(src, &Rc::new(RefCell::new(Environment::default())), &mut interpreter)
+        };
+        assert_eq!(eval_with("\"a\" + true", false), Ok(Value::String("atrue".into())));
+        assert_eq!(eval_with("nil + \"b\"", false), Ok(Value::String("nilb".into())));
+        assert_eq!(eval_with("\"x = \" + 1", false), Ok(Value::String("x = 1".into())));
+        for src in ["\"a\" + true", "\"b\" + nil", "1 + \"x\""] {
+            assert!(eval_with(src, true).is_err(), "{src}");
+        }
+        assert_eq!(eval_with("\"a\" + 'b'", true), Ok(Value::String("ab".into())));
+    }
+
+    fn error_message(res: ValueResult) -> String {
+        match res {
+            Err(EvalError::InvalidExpr(_, Some(msg))) => msg,
+            other => panic!("expected an invalid expression error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn arithmetic_operators() {
+        assert_eq!(eval("7 - 2"), Ok(Value::Double(5.0)));
+        assert_eq!(eval("1 + 2"), Ok(Value::Double(3.0)));
+        assert_eq!(eval("3 * 4"), Ok(Value::Double(12.0)));
+        assert_eq!(eval("8 / 2"), Ok(Value::Double(4.0)));
+        assert_eq!(eval("7 % 3"), Ok(Value::Double(1.0)));
+        assert_eq!(eval("\"a\" + \"b\""), Ok(Value::String("ab".into())));
+        assert_eq!(eval("\"a\" + 1"), Ok(Value::String("a1".into())));
+    }
+
+    #[test]
+    fn plus_resolution_order() {
+        assert_eq!(eval("1 + 2"), Ok(Value::Double(3.0)));
+        assert_eq!(eval("\"1\" + \"2\""), Ok(Value::String("12".into())));
+        assert_eq!(eval("1 + \"2\""), Ok(Value::String("12".into())));
+        assert_eq!(eval("\"1\" + 2"), Ok(Value::String("12".into())));
+        assert_eq!(error_message(eval("true + false")), "Cannot add bool and bool");
+        assert_eq!(error_message(eval("\"a\" + [1]")), "Cannot concatenate string and array");
+        assert_eq!(error_message(eval("[1] + \"a\"")), "Cannot concatenate string and array");
+    }
+
+    #[test]
+    fn modulo_takes_sign_of_dividend() {
+        assert_eq!(eval("-7 % 3"), Ok(Value::Double(-1.0)));
+        assert_eq!(eval("7 % -3"), Ok(Value::Double(1.0)));
+        assert_eq!(eval("-7 % -3"), Ok(Value::Double(-1.0)));
+        assert_eq!(eval("7.5 % 2"), Ok(Value::Double(1.5)));
+    }
+
+    #[test]
+    fn arithmetic_operator_errors() {
+        assert_eq!(error_message(eval("\"a\" - 1")), "Cannot subtract string and number");
+        assert_eq!(error_message(eval("true * 2")), "Cannot multiply bool and number");
+        assert_eq!(error_message(eval("nil / 1")), "Cannot divide nil and number");
+        assert_eq!(error_message(eval("1 % false")), "Cannot apply modulo to number and bool");
+        assert_eq!(error_message(eval("true + 1")), "Cannot add bool and number");
+        assert!(matches!(eval("1 / 0"), Err(EvalError::DivideByZero(_))));
+        assert!(matches!(eval("1 % 0"), Err(EvalError::DivideByZero(_))));
+    }
+
+    #[test]
+    fn ternary_from_source() {
+        assert_eq!(eval("1 < 2 ? 10 : 20"), Ok(Value::Double(10.0)));
+        assert_eq!(eval("1 > 2 ? 10 : 20"), Ok(Value::Double(20.0)));
+    }
+
+    #[test]
+    fn ternary_condition_errors_are_located() {
+        match eval("nil < 1 ? 1 : 2") {
+            Err(EvalError::TernaryCondition(inner, line, col)) => {
+                assert!(matches!(*inner, EvalError::InvalidExpr(..)));
+                assert_eq!((line, col), (1, 9));
+            }
+            other => panic!("expected a ternary condition error, got {other:?}"),
+        }
+        let err = eval("nil < 1 ? 1 : 2").unwrap_err().to_string();
+        assert!(err.contains("in the condition of the ternary"), "{err}");
+        // errors in the branches are left as they are
+        assert!(matches!(eval("true ? 1 / 0 : 2"), Err(EvalError::DivideByZero(_))));
+    }
+
+    #[test]
+    fn errors_inside_groupings_are_located() {
+        match eval("2 + (1 / 0)") {
+            Err(EvalError::InGrouping(inner, group, line, col)) => {
+                assert!(matches!(*inner, EvalError::DivideByZero(_)));
+                assert_eq!((group.as_str(), line, col), ("(1 / 0)", 1, 5));
+            }
+            other => panic!("expected an error inside a grouping, got {other:?}"),
+        }
+        // every enclosing group adds its own context
+        let err = eval("((1 / 0) + 1)").unwrap_err().to_string();
+        assert!(err.ends_with("inside (1 / 0) at line 1, column 2, inside ((1 / 0) + 1) at line 1, column 1"), "{err}");
+    }
+    #[test]
+    fn char_comparison_and_arithmetic() {
+        assert_eq!(eval("'a' < 'b'"), Ok(Value::Bool(true)));
+        assert_eq!(eval("'b' == 'b'"), Ok(Value::Bool(true)));
+        assert_eq!(eval("'a' + 2"), Ok(Value::Char('c')));
+        assert_eq!(eval("'z' - 'a'"), Ok(Value::Double(25.0)));
+        assert_eq!(eval("\"ab\" + 'c'"), Ok(Value::String("abc".into())));
+    }
+    #[test]
+    fn nan_and_infinity() {
+        let env = Rc::new(RefCell::new(Environment::default()));
+        env.define("nan", Value::Double(f64::NAN));
+        // Too big for a double, so it scans as infinity
+        env.define("inf", Value::Double(format!("1{}", "0".repeat(400)).parse().unwrap()));
+        let eval = |src: &str| eval_in(src, &env, &mut Interpreter::default());
+        assert_eq!(eval("nan == nan"), Ok(Value::Bool(false)));
+        assert_eq!(eval("nan != nan"), Ok(Value::Bool(true)));
+        assert_eq!(eval("nan < 1"), Ok(Value::Bool(false)));
+        assert_eq!(eval("1 >= nan"), Ok(Value::Bool(false)));
+        assert_eq!(eval("inf > 1"), Ok(Value::Bool(true)));
+        assert_eq!(eval("-inf"), Ok(Value::Double(f64::NEG_INFINITY)));
+        assert_eq!(eval("\"x\" + inf"), Ok(Value::String("xInfinity".into())));
+        // Comparing a number with a non-number is still an error
+        assert!(eval("nan < \"a\"").is_err());
+    }
+    #[test]
+    fn ternary_evaluates_only_taken_branch() {
+        let env = Rc::new(RefCell::new(Environment::default()));
+        env.define("a", Value::Nil);
+        env.define("b", Value::Nil);
+        let eval = |src: &str| eval_in(src, &env, &mut Interpreter::default());
+        let value = |name: &str| env.borrow().values.get(name).cloned();
+        assert_eq!(eval("true ? (a = 1) : (b = 2)"), Ok(Value::Double(1.0)));
+        assert_eq!((value("a"), value("b")), (Some(Value::Double(1.0)), Some(Value::Nil)));
+        assert_eq!(eval("nil ? (a = 3) : (b = 4)"), Ok(Value::Double(4.0)));
+        assert_eq!((value("a"), value("b")), (Some(Value::Double(1.0)), Some(Value::Double(4.0))));
+        // The untaken branch would fail if it ran
+        assert_eq!(eval("false ? 1 / 0 : 2"), Ok(Value::Double(2.0)));
+    }
+    #[test]
+    fn nil_variable_vs_uninitialized() {
+        let env = Rc::new(RefCell::new(Environment::default()));
+        env.define("x", Value::Nil);
+        env.declare("y");
+        let read = |name: &str| eval_in(name, &env, &mut Interpreter::default());
+        assert_eq!(read("x"), Ok(Value::Nil));
+        assert!(matches!(
+            read("y"),
+            Err(EvalError::VariableEval(RuntimeError::UndefinedVar(_)))
+        ));
+        // Assigning initializes it
+        env.put("y", Value::Double(1.0)).unwrap();
+        assert_eq!(read("y"), Ok(Value::Double(1.0)));
+    }
     #[test]
     fn simple_eval() {
         let mut env = Rc::new(RefCell::new(crate::interpreter::Environment::default())); // Arithmetic
@@ -334,6 +711,720 @@ mod parser_evaluator {
         let res = Parser::new(tokens).run().unwrap().eval(&mut env);
         assert!(res.is_ok());
     }
+    #[test]
+    fn negate_string() {
+        let env = Rc::new(RefCell::new(Environment::default()));
+        let tokens = setup_lox!("-\"abc\"");
+        let res = Parser::new(tokens)
+            .run()
+            .unwrap()
+            .eval(&env, &mut Interpreter::default());
+        match res {
+            Err(EvalError::InvalidExpr(_, Some(msg))) => {
+                assert_eq!(msg, "cannot negate a string")
+            }
+            other => panic!("expected a negation error, got {other:?}"),
+        }
+    }
+    #[test]
+    fn unary_plus() {
+        let env = Rc::new(RefCell::new(Environment::default()));
+        let eval = |tokens| Parser::new(tokens).run().unwrap().eval(&env, &mut Interpreter::default());
+        assert_eq!(eval(setup_lox!("+5")), Ok(Value::Double(5.0)));
+        assert_eq!(eval(setup_lox!("1 - +2")), Ok(Value::Double(-1.0)));
+        match eval(setup_lox!("+\"x\"")) {
+            Err(EvalError::InvalidExpr(_, Some(msg))) => {
+                assert_eq!(msg, "cannot apply unary plus to a string")
+            }
+            other => panic!("expected a unary plus error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn errors_printed_while_parsing_are_not_printed_again() {
+        use crate::parser::error::ParserError;
+        let mut parser = Parser::new(setup_lox!("var a = 1; a + 1 = 2; var = 3;"));
+        parser.parse();
+        // the invalid assignment was printed with its position when it was found
+        assert_eq!(
+            parser.unreported_errors().collect::<Vec<_>>(),
+            [&ParserError::IllegalStmt(Some("Missing variable identifer".into()))]
+        );
+        assert_eq!(parser.take_errors().len(), 2);
+    }
+
+    #[test]
+    fn keyword_misuse() {
+        use crate::parser::error::ParserError;
+        let errors = |tokens| {
+            let mut parser = Parser::new(tokens);
+            parser.parse();
+            parser.take_errors()
+        };
+        assert_eq!(
+            errors(setup_lox!("var class = 1;")),
+            [ParserError::KeywordMisuse("class".into(), "a variable name")]
+        );
+        assert_eq!(
+            errors(setup_lox!("const while = 1;")),
+            [ParserError::KeywordMisuse("while".into(), "an identifier")]
+        );
+        assert_eq!(
+            Parser::new(setup_lox!("1 + var")).run(),
+            Err(ParserError::KeywordMisuse("var".into(), "an expression"))
+        );
+        assert_eq!(
+            ParserError::KeywordMisuse("var".into(), "an expression").to_string(),
+            "Cannot use keyword 'var' as an expression"
+        );
+    }
+
+    #[test]
+    fn constant_if_condition_warns() {
+        let warnings = |tokens| {
+            let mut parser = Parser::new(tokens);
+            parser.parse();
+            assert!(parser.take_errors().is_empty());
+            parser.take_warnings().into_iter().map(|w| w.message).collect::<Vec<_>>()
+        };
+        assert_eq!(
+            warnings(setup_lox!("if (true) print 1; else print 2;")),
+            ["if condition is always true, the else branch is unreachable"]
+        );
+        assert_eq!(
+            warnings(setup_lox!("if (false) print 1;")),
+            ["if condition is always false, the then branch is unreachable"]
+        );
+        assert!(warnings(setup_lox!("if (true) print 1; var a = true; if (a) print 1; else print 2;")).is_empty());
+    }
+
+    #[test]
+    fn assignment_in_for_condition_warns() {
+        let mut parser = Parser::new(setup_lox!("for (var i = 0; i = 5; i = i + 1) {}"));
+        parser.parse();
+        assert!(parser.take_errors().is_empty());
+        let warnings = parser.take_warnings();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].message, "for loop condition assigns to `i`, did you mean `==`?");
+        assert_eq!((warnings[0].line, warnings[0].col), (1, 17));
+
+        let mut parser = Parser::new(setup_lox!("for (var i = 0; i == 5; i = i + 1) {}"));
+        parser.parse();
+        assert!(parser.take_warnings().is_empty());
+    }
+
+    #[test]
+    fn for_desugars_into_while() {
+        use crate::parser::expressions::Expression;
+        use crate::parser::statement::Stmt;
+        use crate::tokenizer::token_type::TokenType;
+        // { var i = 0; while (i < 10) { print i; i = i + 1; } }
+        let stmts = Parser::new(setup_lox!("for (var i = 0; i < 10; i = i + 1) print i;")).parse();
+        let Stmt::Block(outer) = stmts[0].inner() else { panic!("expected a block, got {}", stmts[0]) };
+        assert!(matches!(outer[0].inner(), Stmt::VarDecl { name, .. } if name == "i"));
+        let Stmt::While { condition, body, else_: None } = outer[1].inner() else { panic!("expected a while, got {}", outer[1]) };
+        assert!(matches!(&**condition, Expression::BinExpr(_)));
+        let Stmt::Block(body) = body.inner() else { panic!("expected a block, got {body}") };
+        assert!(matches!(body[0].inner(), Stmt::Print(_)));
+        assert!(matches!(body[1].inner(), Stmt::ExprStmt(update) if matches!(&**update, Expression::Assignment(_))));
+
+        // Without an initializer there's no enclosing block, without an update the body is left alone
+        // and a missing condition is `true`
+        let stmts = Parser::new(setup_lox!("for (;;) break;")).parse();
+        let Stmt::While { condition, body, .. } = stmts[0].inner() else { panic!("expected a while, got {}", stmts[0]) };
+        assert!(matches!(&**condition, Expression::Lit(lit) if lit.inner.r#type == TokenType::TRUE));
+        assert!(matches!(body.inner(), Stmt::Break(None)));
+    }
+
+    #[test]
+    fn newline_terminated_statements() {
+        let scan = |src: &str, newlines: bool| {
+            let mut lox = Lox::new(src.into());
+            lox.newline_terminators = newlines;
+            let mut scanner = Scanner::new(src, &mut lox);
+            scanner.scan_tokens();
+            scanner.tokens
+        };
+        let mut parser = Parser::newline_terminated(scan("print 1\nprint 2", true));
+        assert_eq!(parser.parse().len(), 2);
+        assert!(parser.take_errors().is_empty());
+        // Bodies and `else` can start on the next line, comments end a line too
+        let src = "var x = 1 // one\nif (x == 1)\n{\n  print x\n}\nelse {\n  print 0;\n}\n";
+        let mut parser = Parser::newline_terminated(scan(src, true));
+        assert_eq!(parser.parse().len(), 2);
+        assert!(parser.take_errors().is_empty());
+        // Semicolons still work, and by default they're required
+        let mut parser = Parser::newline_terminated(scan("print 1;\nprint 2;", true));
+        assert_eq!(parser.parse().len(), 2);
+        assert!(parser.take_errors().is_empty());
+        let mut parser = Parser::new(scan("print 1\nprint 2", false));
+        parser.parse();
+        assert!(!parser.take_errors().is_empty());
+    }
+
+    #[test]
+    fn optional_get() {
+        use crate::parser::expressions::{Expression, GetExpr};
+        use crate::tokenizer::token_type::TokenType::QUESTION_DOT;
+        let tokens = setup_lox!("a?.b");
+        assert_eq!(tokens[1].r#type, QUESTION_DOT);
+        let expected = Expression::OptionalGet(GetExpr {
+            object: Box::new(Expression::Variable(tokens[0].clone())),
+            name: tokens[2].clone(),
+        });
+        assert_eq!(Parser::new(tokens).run(), Ok(Box::new(expected)));
+
+        let env = Rc::new(RefCell::new(Environment::default()));
+        let eval = |tokens| Parser::new(tokens).run().unwrap().eval(&env, &mut Interpreter::default());
+        assert_eq!(eval(setup_lox!("nil?.x")), Ok(Value::Nil));
+        assert_eq!(eval(setup_lox!("nil?.x?.y")), Ok(Value::Nil));
+        // a nil found by `?.` skips the rest of the chain, whatever follows
+        assert_eq!(eval(setup_lox!("nil?.x.y")), Ok(Value::Nil));
+        assert_eq!(eval(setup_lox!("nil?.x[0].y()")), Ok(Value::Nil));
+        // but not past a grouping, which ends the chain
+        assert_eq!(eval(setup_lox!("(nil?.x).y")), Err(EvalError::NoProperties("nil")));
+        assert_eq!(eval(setup_lox!("nil.x")), Err(EvalError::NoProperties("nil")));
+        // Anything else goes through to the property access, and there are no instances yet
+        assert_eq!(eval(setup_lox!("1?.x")), Err(EvalError::NoProperties("number")));
+        // A lone `?` is still the ternary
+        assert_eq!(eval(setup_lox!("true ? 1 : 2")), Ok(Value::Double(1.0)));
+    }
+}
+
+mod interpreter_tests {
+    use super::*;
+    use crate::interpreter::{Interpreter, SharedBuffer};
+    use crate::parser::statement::Stmt;
+
+    /// Run `src` the way a file is run and return everything it printed
+    fn run_captured(src: &str) -> String {
+        let mut lox = Lox::new(src.into());
+        let mut scanner = Scanner::new(src, &mut lox);
+        scanner.scan_tokens();
+        let mut interpreter = Interpreter::new(Parser::new(scanner.tokens));
+        let out = SharedBuffer::default();
+        interpreter.set_output(out.clone());
+        interpreter.interpret();
+        out.contents()
+    }
+
+    /// Parse `src` into statements for [Interpreter::run_stmts]
+    fn parse(src: &str) -> Vec<Stmt> {
+        let mut lox = Lox::new(src.into());
+        let mut scanner = Scanner::new(src, &mut lox);
+        scanner.scan_tokens();
+        Parser::new(scanner.tokens).parse()
+    }
+
+    #[test]
+    fn try_catches_runtime_errors() {
+        let out = run_captured("try { print 1; print 1 / 0; print 2; } catch (e) { print e; }");
+        assert!(out.starts_with(">> 1\n>> \"Cannot divide by zero"), "{out}");
+        assert!(!out.contains(">> 2"), "{out}");
+        let out = run_captured(r#"try { if (true) { error("boom"); } } catch (e) { print e; } print 3;"#);
+        assert_eq!(out, ">> \"boom\"\n>> 3\n");
+        // the error is bound as it was raised, whatever expression it was raised in
+        let out = run_captured(r#"try { len(error("boom")); } catch (e) { print e; }"#);
+        assert_eq!(out, ">> \"boom\"\n");
+        let out = run_captured(r#"try { print (error("boom")); } catch (e) { print e; }"#);
+        assert_eq!(out, ">> \"boom\"\n");
+        let out = run_captured(r#"try { print (error("boom")) ? 1 : 2; } catch (e) { print e; }"#);
+        assert_eq!(out, ">> \"boom\"\n");
+        // nothing to catch
+        assert_eq!(run_captured("try { print 1; } catch (e) { print e; }"), ">> 1\n");
+    }
+
+    #[test]
+    fn sort_comparator_with_default_param() {
+        let src = "fun cmp(a, b, rev = false) { return b - a; } var xs = [1, 3, 2]; sort(xs, cmp); print xs;";
+        assert_eq!(run_captured(src), "fn declared <cmp>\nvar xs declared to [1, 3, 2]\n>> [3, 2, 1]\n");
+    }
+
+    #[test]
+    fn functions_print_by_name() {
+        assert_eq!(run_captured("print len;"), ">> <native fn: len>\n");
+        assert_eq!(run_captured("fun add(a, b) {} print add;"), "fn declared <add>\n>> <fn add>\n");
+    }
+
+    #[test]
+    fn partial_application() {
+        let out = run_captured("fun add(a, b) { print a + b; } var inc = add(1); inc(4);");
+        assert_eq!(out, "fn declared <add>\nvar inc declared to <fn add>\n>> 5\n");
+        let curried = "fun add3(a, b, c) { print a + b + c; } add3(1)(2)(3); add3(1, 2)(3); add3(1)(2, 3);";
+        assert_eq!(run_captured(curried), "fn declared <add3>\n>> 6\n>> 6\n>> 6\n");
+        // defaults fill in once the required arguments are there
+        assert_eq!(run_captured("fun f(a, b, c = 10) { print a + b + c; } f(1)(2);"), "fn declared <f>\n>> 13\n");
+        // a call without arguments has nothing to apply
+        assert_eq!(run_captured("fun add(a, b) { print a + b; } add(); add(1)();"), "fn declared <add>\n");
+    }
+
+    #[test]
+    fn return_values() {
+        assert_eq!(run_captured("fun f() { return 3; } print f();"), "fn declared <f>\n>> 3\n");
+        // a bare return, or none at all, gives nil
+        let out = run_captured("fun f() { return; } fun g() {} print f() == nil and g() == nil;");
+        assert!(out.ends_with(">> true\n"), "{out}");
+        // return leaves loops and skips the rest of the body
+        let find = r#"fun find(xs, x) { for (i, y in xs) { if (y == x) return i; } print "missing"; return -1; }"#;
+        let out = run_captured(&format!("{find} print find([5, 6, 7], 6); print find([5], 6);"));
+        assert!(out.ends_with(">> 1\n>> \"missing\"\n>> -1\n"), "{out}");
+        let out = run_captured("fun f() { var i = 0; while (true) { i = i + 1; if (i == 4) return i; } } print f();");
+        assert!(out.ends_with(">> 4\n"), "{out}");
+        let out = run_captured("fun fib(n) { if (n < 2) return n; return fib(n - 1) + fib(n - 2); } print fib(10);");
+        assert!(out.ends_with(">> 55\n"), "{out}");
+        // from inside a while expression
+        let out = run_captured("fun f() { var x = (while (true) { return 5; }); return 1; } print f();");
+        assert!(out.ends_with(">> 5\n"), "{out}");
+        // only inside a function
+        let mut lox = Lox::new("return 1; print 2;".into());
+        assert!(matches!(
+            lox.run(None),
+            Err(crate::error::LoxError::Runtime(crate::parser::error::EvalError::ReturnOutsideFunction))
+        ));
+    }
+
+    #[test]
+    fn set_falsy_changes_conditions() {
+        let branch = r#"if ("") print "A"; else print "B";"#;
+        assert_eq!(run_captured(branch), ">> \"A\"\n");
+        let out = run_captured(&format!(r#"set_falsy(["empty_string"]); {branch} print !"" and !0;"#));
+        assert_eq!(out, ">> \"B\"\n>> false\n");
+        let out = run_captured(&format!(r#"set_falsy(["empty_string"]); set_falsy([]); {branch}"#));
+        assert_eq!(out, ">> \"A\"\n");
+        assert_eq!(run_captured(r#"set_falsy(["zero", "empty_array"]); print 0 or [] or "x";"#), ">> true\n");
+    }
+
+    #[test]
+    fn unordered_values_compare_for_equality_only() {
+        let mut lox = Lox::new(String::new());
+        lox.eval_capture("fun f() {} fun g() {}");
+        assert_eq!(lox.eval_capture("f == f").0, Some(Value::Bool(true)));
+        assert_eq!(lox.eval_capture("f != g").0, Some(Value::Bool(true)));
+        assert_eq!(lox.eval_capture("[1, nil] == [1, nil]").0, Some(Value::Bool(true)));
+        assert_eq!(lox.eval_capture("nil == nil").0, Some(Value::Bool(true)));
+        for src in ["f < g", "f >= f", "[1] < [2]", "nil <= nil"] {
+            assert_eq!(lox.eval_capture(src).0, None, "{src}");
+        }
+    }
+
+    #[test]
+    fn expression_statements_echo_only_in_repl() {
+        assert_eq!(run_captured("{ 5; } 6; if (true) 7; { print 8; }"), ">> 8\n");
+        let mut lox = Lox::new(String::new());
+        assert_eq!(lox.eval_capture("{ 5; } 6;").1, "5\n");
+    }
+
+    #[test]
+    fn for_in_with_index() {
+        assert_eq!(
+            run_captured(r#"for (i, x in ["a", "b"]) print i + " " + x;"#),
+            ">> \"0 a\"\n>> \"1 b\"\n"
+        );
+        assert_eq!(run_captured(r#"for (c in "hi") { if (c == 'i') break; print c; }"#), ">> 'h'\n");
+        // strings can't be indexed, nor numbers iterated
+        assert_eq!(run_captured(r#"for (i, c in "hi") print c; for (x in 1) print x;"#), "");
+    }
+
+    #[test]
+    fn for_loop_sums() {
+        let out = run_captured("var sum = 0; for (var i = 0; i < 10; i = i + 1) sum = sum + i; print sum;");
+        assert!(out.ends_with(">> 45\n"), "{out}");
+        // the loop variable is scoped to the loop
+        let out = run_captured("for (var i = 0; i < 10; i = i + 1) {} print i;");
+        assert!(!out.contains(">> "), "{out}");
+    }
+
+    #[test]
+    fn loop_else_runs_without_break() {
+        let out = run_captured("var i = 0; while (i < 3) { i = i + 1; } else { print \"done\"; }");
+        assert!(out.ends_with(">> \"done\"\n"), "{out}");
+        let out = run_captured("var i = 0; while (i < 3) { i = i + 1; if (i == 2) break; } else { print \"done\"; }");
+        assert!(!out.contains("done"), "{out}");
+        let out = run_captured("for (var i = 0; i < 3; i = i + 1) { if (i == 5) break; } else print i;");
+        assert!(out.ends_with(">> 3\n"), "{out}");
+        let out = run_captured("for (var i = 0; i < 3; i = i + 1) { if (i == 1) break; } else print i;");
+        assert!(!out.contains(">> "), "{out}");
+    }
+
+    #[test]
+    fn else_after_a_loop_in_an_if_belongs_to_the_if() {
+        let program = |ready: bool| {
+            run_captured(&format!(
+                r#"var ready = {ready}; if (ready) for (var i = 0; i < 2; i = i + 1) print i; else print "not ready";"#
+            ))
+        };
+        assert!(program(true).ends_with(">> 0\n>> 1\n"), "{}", program(true));
+        assert!(program(false).ends_with(">> \"not ready\"\n"), "{}", program(false));
+        // even when the loop body is braced
+        let out = run_captured(r#"if (true) while (false) {} else print "not ready";"#);
+        assert!(!out.contains("not ready"), "{out}");
+        // inside braces the loop takes the `else`
+        let out = run_captured(r#"if (true) { while (false) {} else print "done"; }"#);
+        assert!(out.ends_with(">> \"done\"\n"), "{out}");
+    }
+
+    #[test]
+    fn default_parameters() {
+        let greet = r#"fun greet(name, greeting = "hello", end = "!" + name) { print greeting + " " + name + end; }"#;
+        let out = run_captured(&format!(r#"{greet} greet("bob"); greet("bob", "hi"); greet("bob", "hi", "?");"#));
+        assert_eq!(out, "fn declared <greet>\n>> \"hello bob!bob\"\n>> \"hi bob!bob\"\n>> \"hi bob?\"\n");
+        // a parameter without a default can't be left out
+        assert!(!run_captured(&format!("{greet} greet();")).contains(">> "));
+        let mut parser = crate::parser::Parser::new({
+            let src = "fun f(a = 1, b) {}";
+            let mut lox = Lox::new(src.into());
+            let mut scanner = Scanner::new(src, &mut lox);
+            scanner.scan_tokens();
+            scanner.tokens
+        });
+        parser.parse();
+        assert_eq!(parser.take_errors(), [crate::parser::error::ParserError::MissingDefault("b".into())]);
+    }
+
+    #[test]
+    fn eval_capture() {
+        let mut lox = Lox::new(String::new());
+        assert_eq!(lox.eval_capture("print 1; print 2; 3"), (Some(Value::Double(3.0)), "1\n2\n".into()));
+        assert_eq!(lox.eval_capture(r#"print "hi"; print [1, 'c'];"#).1, "\"hi\"\n[1, 'c']\n");
+        // globals carry over between calls, declaring them prints nothing
+        assert_eq!(lox.eval_capture("var a = 5; fun f() {}").1, "");
+        assert_eq!(lox.eval_capture("a * 2;"), (Some(Value::Double(10.0)), String::new()));
+        assert_eq!(lox.eval_capture("print -nil;").0, None);
+        assert_eq!(lox.eval_capture("print (;").0, None);
+    }
+
+    #[test]
+    fn recovers_past_comments() {
+        assert_eq!(run_captured("var = 1; // broken\nprint 2;"), ">> 2\n");
+        assert_eq!(run_captured("print (1; /* broken\n too */ print 3;"), ">> 3\n");
+        assert_eq!(run_captured("1 + ; // a\n/* b */ // c\nvar x = 4; print x;"), "var x declared to 4\n>> 4\n");
+    }
+
+    #[test]
+    fn switch_runs_matching_case() {
+        let src = |x: &str| {
+            format!("switch ({x}) {{ case 1: print \"one\"; case 1 + 1: print \"two\"; print 2; default: print \"other\"; }}")
+        };
+        assert_eq!(run_captured(&src("1")), ">> \"one\"\n");
+        assert_eq!(run_captured(&src("2")), ">> \"two\"\n>> 2\n");
+        assert_eq!(run_captured(&src("\"1\"")), ">> \"other\"\n");
+        // no default, nothing matches
+        assert_eq!(run_captured("switch (3) { case 1: print 1; } print 3;"), ">> 3\n");
+        // break leaves the switch but not the enclosing loop
+        let out = run_captured("var i = 0; while (i < 2) { switch (i) { case 0: print 0; break; print 9; } i = i + 1; } print i;");
+        assert_eq!(out, "var i declared to 0\n>> 0\n>> 2\n");
+    }
+
+    #[test]
+    fn while_expression_breaks_with_value() {
+        let out = run_captured("var i = 0; var x = while (true) { i = i + 1; if (i == 3) break i * 10; }; print x;");
+        assert_eq!(out, "var i declared to 0\nvar x declared to 30\n>> 30\n");
+        // a bare `break` and a loop that runs out both give nil
+        let out = run_captured("var a = while (true) { break; }; var b = while (false) {};");
+        assert_eq!(out, "var a declared to Nil\nvar b declared to Nil\n");
+        // a loop statement drops the value
+        assert_eq!(run_captured("while (true) { break 5; } print 1;"), ">> 1\n");
+    }
+
+    #[test]
+    fn empty_print_prints_blank_line() {
+        assert_eq!(run_captured("print;"), "\n");
+        assert_eq!(run_captured("print 1; print; print 2;"), ">> 1\n\n>> 2\n");
+    }
+
+    #[test]
+    fn runtime_errors_report_line() {
+        let run = |src: &str| {
+            let mut lox = Lox::new(src.into());
+            let mut scanner = Scanner::new(src, &mut lox);
+            scanner.scan_tokens();
+            let mut interpreter = Interpreter::new(Parser::new(scanner.tokens));
+            interpreter.set_output(SharedBuffer::default());
+            let errors = SharedBuffer::default();
+            interpreter.set_error_output(errors.clone());
+            let _ = interpreter.interpret();
+            errors.contents()
+        };
+        let errors = run("var a = 1;\nvar b = 0;\nvar c = a / b;\n");
+        assert!(errors.contains("[line 3]"), "{errors}");
+        // the innermost statement is reported, not the loop around it
+        let errors = run("var i = 0;\nwhile (i < 1) {\n  i = i + 1;\n  i = i + nil;\n}\n");
+        assert!(errors.contains("[line 4]"), "{errors}");
+        // and so is the `else` or `else if` link of an if chain that failed
+        let chain = |tail: &str| format!("var x = 1;\nif (x == 0)\n  print 0;\nelse if (x == 2)\n  print 2;\n{tail}");
+        let errors = run(&chain("else if (x + nil)\n  print 3;\n"));
+        assert!(errors.contains("[line 6]"), "{errors}");
+        let errors = run(&chain("else\n  print x + nil;\n"));
+        assert!(errors.contains("[line 7]"), "{errors}");
+    }
+
+    #[test]
+    fn trace_mode() {
+        let src = "var a = 1 + 2;\nprint a;";
+        let mut lox = Lox::new(src.into());
+        let mut scanner = Scanner::new(src, &mut lox);
+        scanner.scan_tokens();
+        let mut interpreter = Interpreter::new(Parser::new(scanner.tokens));
+        let out = SharedBuffer::default();
+        interpreter.set_output(out.clone());
+        interpreter.set_trace(true);
+        interpreter.interpret().unwrap();
+        let trace = out.contents();
+        let lines: Vec<&str> = trace.lines().filter(|l| l.starts_with("[trace]")).collect();
+        assert!(lines[0].starts_with("[trace] [line 1] VarDecl"), "{trace}");
+        assert!(lines.iter().any(|l| l.ends_with("=> 3")), "{trace}");
+        assert!(lines.iter().any(|l| l.starts_with("[trace] [line 2] PrintStmt")), "{trace}");
+        // off by default
+        assert!(!run_captured("print 1;").contains("[trace]"));
+    }
+
+    #[test]
+    fn fixed_number_format() {
+        let out = run_captured(r#"set_number_format("fixed", 2); print 3.14159;"#);
+        assert_eq!(out, ">> 3.14\n");
+    }
+
+    #[test]
+    fn scientific_number_format() {
+        let out = run_captured(r#"print 1500; set_number_format("scientific", nil); print 1500;"#);
+        assert_eq!(out, ">> 1500\n>> 1.5e3\n");
+    }
+
+    #[test]
+    fn repl_var_decl_echoes_value() {
+        let mut lox = Lox::new(String::new());
+        let out = SharedBuffer::default();
+        lox.repl_interpreter.set_output(out.clone());
+        lox.run(Some("var x = 5;".into())).unwrap();
+        assert_eq!(out.contents(), "var x declared to 5\n>> 5\n");
+        // Files don't echo declarations
+        assert_eq!(run_captured("var x = 5;"), "var x declared to 5\n");
+    }
+
+    #[test]
+    fn destructure_array() {
+        let out = run_captured("var (x, y) = [1, 2]; print x; print y;");
+        assert_eq!(out, "var x declared to 1\nvar y declared to 2\n>> 1\n>> 2\n");
+    }
+
+    #[test]
+    fn destructure_length_mismatch() {
+        let stmts = parse("var (x, y) = [1, 2, 3];");
+        let mut interpreter = Interpreter::default();
+        let env = Rc::new(RefCell::new(Environment::default()));
+        assert_eq!(
+            interpreter.execute(&stmts[0], env, false),
+            Err(crate::parser::error::EvalError::DestructureMismatch(2, 3))
+        );
+    }
+
+    #[test]
+    fn repeat_runs_body_count_times() {
+        let out = run_captured(r#"repeat(3) { print "hi"; }"#);
+        assert_eq!(out, ">> \"hi\"\n".repeat(3));
+        let out = run_captured("var i = 0; repeat(10) { i = i + 1; if (i == 3) break; print i; }");
+        assert_eq!(out, "var i declared to 0\n>> 1\n>> 2\n");
+    }
+
+    #[test]
+    fn repeat_negative_count() {
+        let stmts = parse("repeat(-1) print 1;");
+        let env = Rc::new(RefCell::new(Environment::default()));
+        assert_eq!(
+            Interpreter::default().execute(&stmts[0], env, false),
+            Err(crate::parser::error::EvalError::InvalidRepeatCount("-1".into()))
+        );
+    }
+
+    #[test]
+    fn iteration_limit_aborts_loops() {
+        let mut interpreter = Interpreter::default();
+        interpreter.set_iteration_limit(Some(100));
+        let limit = Err(crate::parser::error::EvalError::IterationLimitExceeded(100));
+        assert_eq!(interpreter.run_stmts(&parse("while (true) {}")), limit);
+        assert_eq!(interpreter.run_stmts(&parse("for (; true; 0) {}")), limit);
+        // loops that finish within the limit are unaffected
+        assert_eq!(interpreter.run_stmts(&parse("var i = 0; while (i < 100) i = i + 1;")), Ok(Value::Nil));
+    }
+
+    #[test]
+    fn scope_nesting_is_limited() {
+        let nested_ifs = |n: usize| format!("{}print 1;", "if (true) ".repeat(n));
+        let mut interpreter = Interpreter::default();
+        interpreter.set_output(SharedBuffer::default());
+        interpreter.set_scope_limit(8);
+        assert_eq!(interpreter.run_stmts(&parse(&nested_ifs(3))), Ok(Value::Double(1.0)));
+        assert_eq!(
+            interpreter.run_stmts(&parse(&nested_ifs(20))),
+            Err(crate::parser::error::EvalError::ScopeTooDeep(8))
+        );
+
+        let global = Rc::new(RefCell::new(Environment::default()));
+        let block = Rc::new(RefCell::new(Environment::enclosed_by(Rc::clone(&global))));
+        let inner = Environment::loop_enclosed_by(block);
+        assert_eq!((global.borrow().depth(), inner.depth()), (1, 3));
+    }
+
+    #[test]
+    fn const_cannot_be_reassigned() {
+        let mut interpreter = Interpreter::default();
+        assert_eq!(
+            interpreter.run_stmts(&parse("const x = 1; x = 2;")),
+            Err(crate::parser::error::EvalError::VariableEval(
+                crate::parser::error::RuntimeError::AssignToConst("x".into())
+            ))
+        );
+        // the failed assignment leaves the value alone, and a nested scope can't assign it either
+        assert_eq!(interpreter.run_stmts(&parse("print x;")), Ok(Value::Double(1.0)));
+        assert!(interpreter.run_stmts(&parse("if (true) x = 3;")).is_err());
+        assert_eq!(interpreter.run_stmts(&parse("print x + 1;")), Ok(Value::Double(2.0)));
+        // redeclaring with `var`, with or without an initializer, makes it assignable again
+        assert_eq!(interpreter.run_stmts(&parse("const a = 1; var a; a = 2; print a;")), Ok(Value::Double(2.0)));
+        assert_eq!(interpreter.run_stmts(&parse("const b = 1; var b = 2; b = 3; print b;")), Ok(Value::Double(3.0)));
+    }
+
+    #[test]
+    fn run_stmts_reuses_parsed_program() {
+        let mut interpreter = Interpreter::default();
+        let out = SharedBuffer::default();
+        interpreter.set_output(out.clone());
+        interpreter.run_stmts(&parse("var count = 0;")).unwrap();
+        let program = parse("count = count + 1; print count;");
+        assert_eq!(interpreter.run_stmts(&program), Ok(Value::Double(1.0)));
+        assert_eq!(interpreter.run_stmts(&program), Ok(Value::Double(2.0)));
+        assert_eq!(out.contents(), "var count declared to 0\n>> 1\n>> 2\n");
+    }
+
+    #[test]
+    fn block_scoped_function_does_not_leak() {
+        let stmts = parse("{ fun f() { print 1; } f(); } f();");
+        let mut interpreter = Interpreter::default();
+        let out = SharedBuffer::default();
+        interpreter.set_output(out.clone());
+        assert_eq!(interpreter.run_stmts(&stmts[..1]), Ok(Value::Nil));
+        assert_eq!(out.contents(), "fn declared <f>\n>> 1\n");
+        assert!(matches!(
+            interpreter.run_stmts(&stmts[1..]),
+            Err(crate::parser::error::EvalError::FunctionUndefined(_))
+        ));
+    }
+
+    #[test]
+    fn empty_blocks_and_function_bodies() {
+        let stmts = parse("{} if (true) {} else {} while (false) {} fun f() {} f(); print f();");
+        let mut interpreter = Interpreter::default();
+        let out = SharedBuffer::default();
+        interpreter.set_output(out.clone());
+        for stmt in &stmts {
+            assert_eq!(interpreter.run_stmts(std::slice::from_ref(stmt)), Ok(Value::Nil), "{stmt}");
+        }
+        assert_eq!(out.contents(), "fn declared <f>\n");
+    }
+
+    #[test]
+    fn repl_runs_only_new_lines() {
+        let mut lox = Lox::new(String::new());
+        let out = SharedBuffer::default();
+        lox.repl_interpreter.set_output(out.clone());
+        lox.run(Some("print 1;".into())).unwrap();
+        lox.run(Some("print 2;".into())).unwrap();
+        assert_eq!(out.contents(), ">> 1\n>> 2\n");
+    }
+
+    #[test]
+    fn else_if_chain() {
+        let chain = "if (x == 1) print 1; else if (x == 2) print 2; else if (x == 3) print 3; \
+                     else if (x == 4) print 4; else if (x == 5) print 5; else print 0;";
+        for (x, taken) in [(1, 1), (2, 2), (3, 3), (4, 4), (5, 5), (6, 0)] {
+            let out = run_captured(&format!("var x = {x}; {chain}"));
+            assert_eq!(out, format!("var x declared to {x}\n>> {taken}\n"));
+        }
+    }
+}
+
+mod lox_errors {
+    use super::*;
+    use crate::error::LoxError;
+    use crate::parser::error::EvalError;
+
+    fn run(src: &str) -> Result<(), LoxError> {
+        let mut lox = Lox::new(src.into());
+        lox.run(None)
+    }
+
+    #[test]
+    fn success() {
+        assert_eq!(run("var a = 1; a = a + 1;"), Ok(()));
+    }
+
+    #[test]
+    fn scan_error() {
+        match run("var a = 1 @ 2;") {
+            Err(LoxError::Scan(diagnostics)) => {
+                assert_eq!(diagnostics.len(), 1);
+                assert_eq!((diagnostics[0].line, diagnostics[0].col), (1, 11));
+            }
+            other => panic!("expected a scan error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn parse_error() {
+        assert!(matches!(run("var = 1;"), Err(LoxError::Parse(errors)) if errors.len() == 1));
+    }
+
+    #[test]
+    fn return_outside_function() {
+        assert!(matches!(run("return;"), Err(LoxError::Runtime(EvalError::ReturnOutsideFunction))));
+        assert!(matches!(
+            run("if (true) return 1; print 2;"),
+            Err(LoxError::Runtime(EvalError::ReturnOutsideFunction))
+        ));
+        assert_eq!(run("fun f() { return; } f();"), Ok(()));
+    }
+
+    #[test]
+    fn break_in_loop_else_needs_an_enclosing_loop() {
+        assert!(matches!(run("while (false) {} else break;"), Err(LoxError::Runtime(EvalError::BreakWithout))));
+        assert_eq!(run("var n = 0; while (n < 1) { n = n + 1; while (false) {} else { break; } }"), Ok(()));
+    }
+
+    #[test]
+    fn error_summary_counts_every_error() {
+        let mut lox = Lox::new("print 1 / 0; print 2; print nil + 1;".into());
+        assert!(matches!(lox.run(None), Err(LoxError::Runtime(_))));
+        assert_eq!(lox.error_summary(), Some("2 errors".into()));
+        let mut lox = Lox::new("var = 1; var = 2;".into());
+        assert!(lox.run(None).is_err());
+        assert_eq!(lox.error_summary(), Some("2 errors".into()));
+        let mut lox = Lox::new("var a = 1;".into());
+        assert_eq!(lox.run(None), Ok(()));
+        assert_eq!(lox.error_summary(), None);
+    }
+
+    #[test]
+    fn incomplete_repl_line() {
+        use crate::tokenizer::token_type::TokenType;
+        let mut lox = Lox::new(String::new());
+        assert_eq!(
+            lox.run(Some("1 +".into())),
+            Err(LoxError::Incomplete(TokenType::MISSING_OPERAND))
+        );
+        assert_eq!(lox.run(Some("{ print 1;".into())), Err(LoxError::Incomplete(TokenType::RIGHT_BRACE)));
+        assert!(matches!(lox.run(Some("1 + @".into())), Err(LoxError::Scan(_))));
+        // Files don't wait for more input
+        assert!(matches!(run("1 +"), Err(LoxError::Parse(_))));
+    }
+
+    #[test]
+    fn runtime_error() {
+        let mut lox = Lox::new("var a = -\"x\";".into());
+        assert!(matches!(
+            lox.run(None),
+            Err(LoxError::Runtime(EvalError::InvalidExpr(..)))
+        ));
+        assert!(lox.had_runtime_error);
+    }
 }
 
 // mod statements {
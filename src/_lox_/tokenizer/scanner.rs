@@ -26,11 +26,21 @@ lazy_static! {
         h.insert("true", TRUE);
         h.insert("var", VAR);
         h.insert("while", WHILE);
+        h.insert("repeat", REPEAT);
+        h.insert("const", CONST);
+        h.insert("try", TRY);
+        h.insert("catch", CATCH);
+        h.insert("switch", SWITCH);
+        h.insert("case", CASE);
+        h.insert("default", DEFAULT);
+        h.insert("in", IN);
         h
     };
 }
 
 use super::{token::Token, token_type::TokenType};
+/// Default for [Scanner::set_max_tokens], far more than any reasonable program needs
+pub const DEFAULT_MAX_TOKENS: usize = 1_000_000;
 #[derive(Debug)]
 // TODO : at this point source is a reference to Lox which is why we are trying to get a &mut and & from the same instance
 // This means every time we pass a source string we have to unnecessarily clone it and then pass a reference to it. There's room
@@ -52,11 +62,15 @@ pub struct Scanner<'a: 'b, 'b> {
     pub(crate) tokens: Vec<Token>,
     /// Pointer to our Lox instance
     pub(crate) lox: &'b mut Lox,
+    /// Scanning stops with an error once this many tokens have been produced
+    max_tokens: Option<usize>,
 }
 #[allow(unused)]
 impl<'a, 'b> Scanner<'a, 'b> {
     /// Create a scanner that's ready to be used with scan_tokens
+    /// A leading UTF-8 byte order mark is not part of the program and is skipped
     pub fn new(source: &'a str, lox: &'b mut Lox) -> Self {
+        let source = source.strip_prefix('\u{FEFF}').unwrap_or(source);
         let char_indices = source.char_indices().better_peekable();
         Self {
             source,
@@ -67,8 +81,14 @@ impl<'a, 'b> Scanner<'a, 'b> {
             tokens: vec![],
             chars: char_indices,
             col: 0, // Initial offset is already set as advance will increment this on each line
+            max_tokens: Some(DEFAULT_MAX_TOKENS),
         }
     }
+    /// Stop scanning with an error once `limit` tokens have been produced, guarding against pathological
+    /// input. `None` removes the limit, the default is [DEFAULT_MAX_TOKENS]
+    pub fn set_max_tokens(&mut self, limit: Option<usize>) {
+        self.max_tokens = limit;
+    }
     /// Note the trailing 's', different from scan_token()
     pub fn scan_tokens(&mut self) {
         // Each turn of this loop should consume as many characters as it wants
@@ -77,6 +97,11 @@ impl<'a, 'b> Scanner<'a, 'b> {
             // initialize start to the beginning of next lexeme
             self.start = self.current;
             let _next = self.scan_single_token();
+            if let Some(limit) = self.max_tokens && self.tokens.len() > limit {
+                self.tokens.truncate(limit);
+                self.lox.scan_error(self.line, self.col, format!("Too many tokens, scanning stopped after {limit}"));
+                break;
+            }
         }
         self.tokens
             .push(Token::new(TokenType::EOF, "".into(), self.line, self.col));
@@ -101,9 +126,10 @@ impl<'a, 'b> Scanner<'a, 'b> {
     }
     /// Consume the iterator, increment `current` offset and return the next char, returns "" if nothing left
     /// If line breaks encountered, incremenet line number
+    /// `current` is a byte offset so it moves by the encoded length of the char
     fn advance(&mut self) -> Option<char> {
         if let Some((_pos, next_char)) = self.chars.next() {
-            self.current += 1;
+            self.current += next_char.len_utf8();
             self.col += 1;
 
             // In case our current char is a new line, set self.col = 0 because on next advance call
@@ -141,14 +167,32 @@ impl<'a, 'b> Scanner<'a, 'b> {
             '[' => self.add_token(TokenType::LEFT_SQUARE),
             ']' => self.add_token(TokenType::RIGHT_SQUARE),
             ',' => self.add_token(TokenType::COMMA),
+            // A decimal point never gets here, scan_number consumes it along with the digits
+            '.' => self.add_token(TokenType::DOT),
             '-' => self.add_token(TokenType::MINUS),
             '+' => self.add_token(TokenType::PLUS),
             '%' => self.add_token(TokenType::MODULUS),
             '*' => self.add_token(TokenType::STAR),
             ';' => self.add_token(TokenType::SEMICOLON),
-            '?' => self.add_token(TokenType::TERNARYC),
+            '?' => {
+                if self.next_match('.') {
+                    self.add_token(TokenType::QUESTION_DOT);
+                } else {
+                    self.add_token(TokenType::TERNARYC);
+                }
+            }
             ':' => self.add_token(TokenType::TERNARYE),
+            '\n' if self.lox.newline_terminators => self.add_token(TokenType::NEWLINE),
             ' ' | '\n' | '\t' | '\r' => {}
+            // Line continuation: a `\` right before a line break joins the two lines, the break still
+            // counts towards line numbers but never becomes a NEWLINE token. A `\` anywhere else is left
+            // to the catch all below
+            '\\' if self.peek() == Some('\n') || (self.peek() == Some('\r') && self.peek_next() == Some('\n')) => {
+                if self.peek() == Some('\r') {
+                    self.advance();
+                }
+                self.advance();
+            }
             // Single or Double character lexemes: !, !=, <, <=, >, >=
             '!' => {
                 // ! are a part of a lexeme "!=" just like "<=" or ">="
@@ -205,7 +249,7 @@ impl<'a, 'b> Scanner<'a, 'b> {
                                 self.advance();
                             }
                             // EOF
-                            Lox::report_syntax_err(
+                            self.lox.scan_error(
                                 self.line,
                                 self.col,
                                 format!("Unclosed comment"),
@@ -231,6 +275,11 @@ impl<'a, 'b> Scanner<'a, 'b> {
                 let col = self.col;
                 self.scan_string(col);
             }
+            // Char literal
+            '\'' => {
+                let col = self.col;
+                self.scan_char(col);
+            }
             // Scan for a Number literal
             c if c.is_ascii_digit() => {
                 // Numbers start with digit, negative numbers don't, instead -123 is to be read as an expression
@@ -244,11 +293,10 @@ impl<'a, 'b> Scanner<'a, 'b> {
                 self.identifier_or_keyword(col);
             }
             unexpected => {
-                self.lox.had_error = true; // Notify the lox machine that error has encountered so we can ignore running the file
-                                           // however we must continue scanning tokens
+                // Notify the lox machine that error has encountered so we can ignore running the file
+                // however we must continue scanning tokens
                 let q = if unexpected == '\'' { ' ' } else { '\'' };
-                self.lox.had_error = true;
-                Lox::report_syntax_err(
+                self.lox.scan_error(
                     self.line,
                     self.col,
                     format!("Unexpected character {q}{unexpected}{q}"),
@@ -267,7 +315,7 @@ impl<'a, 'b> Scanner<'a, 'b> {
         if let Some(&(_, next_ch)) = self.chars.peek() {
             if next_ch == expected {
                 // Only advance "current" if the next char is what we expected
-                self.current += 1;
+                self.current += expected.len_utf8();
                 self.chars.next(); // Also advance our iterator to keep up with `current`
                 return true;
             } else {
@@ -278,38 +326,133 @@ impl<'a, 'b> Scanner<'a, 'b> {
         }
     }
 
-    /// Scan as string, upto next `"`, omitting start and end `"`
+    /// Scan a string literal, omitting the quotes. Escape sequences are processed as they're
+    /// read so the token lexeme holds the cooked string
     fn scan_string(&mut self, string_col_start: usize) {
+        let mut text = String::new();
         while let Some(char) = self.advance() {
             if char == '"' {
-                let lexeme_text = &self.source[self.start + 1..self.current - 1];
                 self.tokens.push(Token::new(
                     TokenType::STRING,
-                    lexeme_text.into(),
+                    text,
                     self.line,
                     string_col_start,
                 ));
                 return;
-            } else if self.is_at_end() {
+            } else if char == '\\' {
+                match self.advance() {
+                    Some(e) => match Self::unescape(e) {
+                        Some(c) => text.push(c),
+                        None => {
+                            self.lox.scan_error(
+                                self.line,
+                                self.col,
+                                format!("Unknown escape sequence '\\{e}' in string"),
+                            );
+                            text.push(char);
+                            text.push(e);
+                        }
+                    },
+                    // A trailing backslash, reported as unclosed below
+                    None => {}
+                }
+            } else {
+                text.push(char);
+            }
+            if self.is_at_end() {
                 let message = format!("Unclosed string");
-                self.lox.had_error = true;
-                Lox::report_syntax_err(self.line, self.col, message)
+                self.lox.scan_error(self.line, self.col, message)
+            }
+        }
+    }
+    /// Scan a char literal like `'a'` or `'\n'`, omitting the quotes. The token lexeme
+    /// holds the unescaped character
+    fn scan_char(&mut self, col: usize) {
+        let ch = match self.advance() {
+            Some('\\') => match self.advance().map(|e| (e, Self::unescape(e))) {
+                Some((_, Some(c))) => Some(c),
+                Some((e, None)) => {
+                    self.lox.scan_error(
+                        self.line,
+                        self.col,
+                        format!("Unknown escape sequence '\\{e}' in char literal"),
+                    );
+                    None
+                }
+                None => None,
+            },
+            Some('\'') => {
+                self.lox.scan_error(self.line, self.col, format!("Empty char literal"));
+                return;
+            }
+            c => c,
+        };
+        if self.next_match('\'') {
+            if let Some(ch) = ch {
+                self.tokens
+                    .push(Token::new(TokenType::CHAR, ch.into(), self.line, col));
+            }
+            return;
+        }
+        // Skip the rest of the literal (up to the closing quote on this line) so that scanning resumes after it
+        let mut closed = false;
+        while let Some(c) = self.peek() {
+            if c == '\n' {
+                break;
+            }
+            self.advance();
+            if c == '\'' {
+                closed = true;
+                break;
             }
         }
+        let message = if closed {
+            format!("Char literal {} must contain exactly one character", self.current_lexeme())
+        } else {
+            format!("Unclosed char literal")
+        };
+        self.lox.scan_error(self.line, self.col, message);
+    }
+    /// The character denoted by the escape sequence `\c`, if it's a known one
+    fn unescape(c: char) -> Option<char> {
+        match c {
+            'n' => Some('\n'),
+            't' => Some('\t'),
+            'r' => Some('\r'),
+            '0' => Some('\0'),
+            '\\' => Some('\\'),
+            '\'' => Some('\''),
+            '"' => Some('"'),
+            _ => None,
+        }
     }
-    /// Scan as number
+    /// Scan as number. `_` may separate digits, as in `1_000_000`, and is kept in the lexeme
     fn scan_number(&mut self, col: usize) {
         let mut decimal_set = false;
+        // The first digit has already been consumed
+        let mut prev_digit = true;
+        let mut misplaced_separator = false;
 
         // Note this loop body won't execute if peek() returns None as in case of EOF
         while let Some(c) = self.peek() {
             if c.is_ascii_digit() {
                 self.advance();
+                prev_digit = true;
+                continue;
+            }
+            if c == '_' {
+                self.advance();
+                // Only allowed between two digits
+                if !prev_digit || !matches!(self.peek(), Some(d) if d.is_ascii_digit()) {
+                    misplaced_separator = true;
+                }
+                prev_digit = false;
                 continue;
             }
             if c == '.' && !decimal_set {
                 decimal_set = true;
                 self.advance();
+                prev_digit = false;
                 continue;
             }
             // Signifies end of number. Also catches double decimal points
@@ -319,12 +462,21 @@ impl<'a, 'b> Scanner<'a, 'b> {
             }
         }
         self.add_token_col(TokenType::NUMBER, col);
+        if misplaced_separator {
+            self.lox.scan_error(
+                self.line,
+                col,
+                format!(
+                    "'_' in {} must be between two digits",
+                    &self.source[self.start..self.current]
+                ),
+            );
+        }
 
         // We know numbers are never followed by alphabets, yet they maybe followed my math ops or maybe another decimal?
         if let Some(c) = self.peek() {
             if c.is_alphabetic() || (decimal_set && c == '.') {
-                self.lox.had_error = true;
-                Lox::report_syntax_err(
+                self.lox.scan_error(
                     self.line,
                     self.col,
                     format!(
@@ -27,6 +27,15 @@ impl Token {
             col,
         }
     }
+    /// Is this one of Lox's reserved words? A keyword can't be used as an identifier
+    pub fn is_keyword(&self) -> bool {
+        use TokenType::*;
+        matches!(
+            self.r#type,
+            AND | OR | CLASS | ELSE | FALSE | FUN | FOR | IF | NIL | PRINT | RETURN | SUPER | THIS | TRUE
+                | VAR | WHILE | BREAK | REPEAT | CONST | TRY | CATCH | SWITCH | CASE | DEFAULT | IN
+        )
+    }
     /// Returns the location as a string 
     pub fn location(&self) -> String {
         format!("{}{}{}{}", "line ",self.ln, " col ", self.col )
@@ -35,6 +44,9 @@ impl Token {
     pub fn to_string(&self) -> String {
         let mut q = '"';
         let mut line_beginning = self.ln;
+        if self.r#type == TokenType::CHAR {
+            q = '\'';
+        }
         if self.r#type == TokenType::STRING {
             q = '"'; // Note that we already trim out the quotes from source string during scan_string
                      // Offset by new lines if a multi string is present
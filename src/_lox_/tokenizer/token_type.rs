@@ -26,11 +26,13 @@ pub enum TokenType {
     LESS_EQUAL,    // <=
     TERNARYC,      // ? Ternary operator condition
     TERNARYE,      // : Ternary operator else
+    QUESTION_DOT,  // ?. Optional property access
 
     // Literals
     IDENTIFIER,
     STRING,
     NUMBER,
+    CHAR,
 
     // Keywords
     AND,
@@ -54,10 +56,20 @@ pub enum TokenType {
 
     MULTI_LINE_COMMENT,
     COMMENT,
+    /// Line break, only scanned when [Lox::newline_terminators](crate::Lox::newline_terminators) is on
+    NEWLINE,
     MISSING_OPERAND,
 
     // New addition
     BREAK,
+    REPEAT,
+    CONST,
+    TRY,
+    CATCH,
+    SWITCH,
+    CASE,
+    DEFAULT,
+    IN,
 }
 
 use crate::tokenizer::token_type::TokenType::*;
@@ -66,7 +78,7 @@ impl TokenType {
     /// This may be subject to change as the parser progresses or maybe removed entirely
     pub fn is_primary(&self) -> bool {
         match self {
-           NIL | FALSE | TRUE | STRING | IDENTIFIER | NUMBER => true,
+           NIL | FALSE | TRUE | STRING | CHAR | IDENTIFIER | NUMBER => true,
            _ => false
         }
     }
@@ -100,6 +112,7 @@ impl ToString for TokenType {
             IDENTIFIER => "some identifer",
             STRING => "some string",
             NUMBER => "some number",
+            CHAR => "some char",
             AND => "and",
             CLASS => "class",
             ELSE => "else",
@@ -119,10 +132,20 @@ impl ToString for TokenType {
             EOF => "eof",
             MULTI_LINE_COMMENT => "multi-line comment",
             COMMENT => "single-line comment",
+            NEWLINE => "newline",
             TERNARYC => "?",
             TERNARYE => ":",
+            QUESTION_DOT => "?.",
             MISSING_OPERAND => "Missing Operand",
             BREAK => "Break",
+            REPEAT => "repeat",
+            CONST => "const",
+            TRY => "try",
+            CATCH => "catch",
+            SWITCH => "switch",
+            CASE => "case",
+            DEFAULT => "default",
+            IN => "in",
         };
         str.to_string()
     }
@@ -1,35 +1,85 @@
+use _lox_::error::LoxError;
+use _lox_::tokenizer::token_type::TokenType;
 use _lox_::Lox;
+use colored::Colorize;
 use std::fs::File;
 use std::io::Read;
+/// What the command line asked for
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    /// `--version`, print the crate version and exit
+    Version,
+    /// Run the Lox file at this path
+    File(String),
+    /// No arguments, start the REPL
+    Repl,
+    /// Anything else
+    Usage,
+}
+/// Work out the [Command] from `args`, `args[0]` being the program name
+pub fn parse_args(args: &[String]) -> Command {
+    match args {
+        [_] | [] => Command::Repl,
+        [_, flag] if flag == "--version" => Command::Version,
+        [_, path] => Command::File(path.clone()),
+        _ => Command::Usage,
+    }
+}
 /// Start a REPL for Lox if no CLI args are passed
 /// Or, accept a file path, parse it and try running it as a Lox file
 pub fn run_cli() {
     let args = std::env::args().collect::<Vec<String>>();
-    if args.len() == 2 {
-        // TODO: This is unreliable
-        let mut file_path = File::open(&args[1]).expect(&format!("Cannot open file {}", &args[1]));
-        let mut file = String::new();
-        file_path
-            .read_to_string(&mut file)
-            .expect("Cannot access file path {file_path}");
-        run_file(file.as_ref());
-    } else if repl::start_repl().is_err() {
-        panic!("REPL error");
-    } else if args.len() > 2 {
-        eprintln!("Usage \"loxr {{lox file}}\"");
+    match parse_args(&args) {
+        Command::Version => println!("loxr {}", env!("CARGO_PKG_VERSION")),
+        Command::File(path) => {
+            // TODO: This is unreliable
+            let mut file_path = File::open(&path).expect(&format!("Cannot open file {path}"));
+            let mut file = String::new();
+            file_path
+                .read_to_string(&mut file)
+                .expect("Cannot access file path {file_path}");
+            run_file(file.as_ref());
+        }
+        Command::Repl => {
+            if repl::start_repl(&repl::ReplConfig::from_env()).is_err() {
+                panic!("REPL error");
+            }
+        }
+        Command::Usage => eprintln!("Usage \"loxr {{lox file}}\" or \"loxr --version\""),
     }
 }
 pub fn run_file(file: &str) {
     let mut lox = Lox::new(file.into());
-    lox.run(None);
-    if lox.had_runtime_error {
-        std::process::exit(70);
+    let result = lox.run(None);
+    if let Err(err) = &result {
+        report(err);
+    }
+    // Printed after all the errors so that it's the last thing a long run shows
+    if let Some(summary) = lox.error_summary() {
+        eprintln!("{}", summary.red());
+    }
+    match result {
+        Ok(()) => {}
+        Err(LoxError::Runtime(_)) => std::process::exit(70),
+        Err(_) => std::process::exit(65),
+    }
+}
+/// Print scan errors. Parse errors have already been reported by the parser, and runtime errors by the interpreter
+fn report(err: &LoxError) {
+    match err {
+        LoxError::Scan(diagnostics) => {
+            for d in diagnostics {
+                Lox::report_syntax_err(d.line, d.col, d.message.clone());
+            }
+        }
+        LoxError::Parse(_) | LoxError::Incomplete(_) | LoxError::Runtime(_) => {}
     }
 }
 
 mod repl {
     use super::*;
     use rustyline::{error::ReadlineError, Editor};
+    use std::path::PathBuf;
     // use rustyline::validate::MatchingBracketValidator;
     // use rustyline::{Cmd, EventHandler, KeyCode, KeyEvent, Modifiers};
     // use rustyline_derive::{Completer, Helper, Highlighter, Hinter, Validator};
@@ -40,28 +90,81 @@ mod repl {
     //     brackets: MatchingBracketValidator,
     // }
 
+    /// Prompt and history file of the REPL
+    #[derive(Debug, Clone, PartialEq)]
+    pub(crate) struct ReplConfig {
+        pub prompt: String,
+        /// History is loaded from here when the REPL starts
+        pub history_path: PathBuf,
+    }
+
+    impl Default for ReplConfig {
+        fn default() -> Self {
+            Self { prompt: "Lox > ".into(), history_path: "history.txt".into() }
+        }
+    }
+
+    impl ReplConfig {
+        /// The defaults, overridden by `LOXR_PROMPT` and `LOXR_HISTORY` when they're set
+        pub(crate) fn from_env() -> Self {
+            let mut config = Self::default();
+            if let Ok(prompt) = std::env::var("LOXR_PROMPT") {
+                config.prompt = prompt;
+            }
+            if let Ok(path) = std::env::var("LOXR_HISTORY") {
+                config.history_path = path.into();
+            }
+            config
+        }
+    }
+
+    /// Where the REPL reads its lines from, a terminal editor outside of tests
+    pub(crate) trait LineReader {
+        fn readline(&mut self, prompt: &str) -> Result<String, ReadlineError>;
+        fn add_history_entry(&mut self, line: &str);
+        fn load_history(&mut self, path: &std::path::Path) -> Result<(), ReadlineError>;
+    }
+
+    impl LineReader for Editor<()> {
+        fn readline(&mut self, prompt: &str) -> Result<String, ReadlineError> {
+            Editor::readline(self, prompt)
+        }
+        fn add_history_entry(&mut self, line: &str) {
+            Editor::add_history_entry(self, line);
+        }
+        fn load_history(&mut self, path: &std::path::Path) -> Result<(), ReadlineError> {
+            Editor::load_history(self, path)
+        }
+    }
+
+    pub(crate) fn start_repl(config: &ReplConfig) -> std::io::Result<()> {
+        let mut rl = Editor::<()>::new().expect("rustyline failed");
+        run_repl(&mut rl, config)
+    }
+
     #[allow(unreachable_code)]
-    pub(crate) fn start_repl() -> std::io::Result<()> {
+    pub(crate) fn run_repl(rl: &mut impl LineReader, config: &ReplConfig) -> std::io::Result<()> {
         let mut lox_interpreter = Lox::new(Default::default());
         #[allow(unused_assignments)]
         let mut buf = String::new();
+        // Lines of a statement that isn't complete yet
+        let mut pending = String::new();
         // let h = InputValidator {
         //     brackets: MatchingBracketValidator::new(),
         // };
-        let mut rl = Editor::<()>::new().expect("rustyline failed");
         // rl.set_helper(Some(h));
         // rl.bind_sequence(
         //     KeyEvent(KeyCode::Char('s'), Modifiers::CTRL),
         //     EventHandler::Simple(Cmd::Newline),
         // );
-        if rl.load_history("history.txt").is_err() {
+        if rl.load_history(&config.history_path).is_err() {
             // println!("No previous history.");
         }
         loop {
-            let line = rl.readline("Lox > ");
+            let line = rl.readline(if pending.is_empty() { &config.prompt } else { "... " });
             match line {
                 Ok(line) => {
-                    rl.add_history_entry(line.as_str());
+                    rl.add_history_entry(&line);
                     buf = line;
                 }
                 Err(ReadlineError::Interrupted) => {
@@ -84,20 +187,83 @@ mod repl {
                 println!("Exiting Lox interpreter");
                 std::process::exit(0);
             }
+            if input == ":reset" {
+                lox_interpreter.repl_interpreter.reset_globals();
+                println!("Global environment reset");
+                continue;
+            }
             if input.starts_with("//") || input.starts_with("/*") && input.ends_with("*/") {
                 continue;
             }
-            if let Some(semicolon) = input.chars().last() {
-                if semicolon != ';' && semicolon != '}' {
-                    let mut s = input.to_string();
-                    s.push(';');
-                    lox_interpreter.run(Some(s));
-                    continue;
-                }
+            let mut src = format!("{pending}{input}");
+            pending.clear();
+            let mut result = lox_interpreter.run(Some(src.clone()));
+            // A line that only lacks its final `;` is completed for the user
+            if let Err(LoxError::Incomplete(TokenType::SEMICOLON)) = result {
+                src.push(';');
+                result = lox_interpreter.run(Some(src.clone()));
+            }
+            match result {
+                Ok(()) => {}
+                // Keep what we have and prompt for the rest
+                Err(LoxError::Incomplete(_)) => pending = src + "\n",
+                Err(err) => report(&err),
             }
-            lox_interpreter.run(Some(String::from(input)));
             buf.clear();
         }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::repl::*;
+    use super::{parse_args, Command};
+    use rustyline::error::ReadlineError;
+    use std::collections::VecDeque;
+    use std::path::{Path, PathBuf};
+
+    /// Hands out scripted lines and records what the REPL asked of it
+    #[derive(Default)]
+    struct Script {
+        lines: VecDeque<&'static str>,
+        prompts: Vec<String>,
+        history: Vec<String>,
+        loaded_from: Option<PathBuf>,
+    }
+
+    impl LineReader for Script {
+        fn readline(&mut self, prompt: &str) -> Result<String, ReadlineError> {
+            self.prompts.push(prompt.into());
+            self.lines.pop_front().map(String::from).ok_or(ReadlineError::Eof)
+        }
+        fn add_history_entry(&mut self, line: &str) {
+            self.history.push(line.into());
+        }
+        fn load_history(&mut self, path: &Path) -> Result<(), ReadlineError> {
+            self.loaded_from = Some(path.into());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn repl_uses_config() {
+        let config = ReplConfig { prompt: "lox> ".into(), history_path: std::env::temp_dir().join("loxr-history.txt") };
+        let mut script = Script { lines: ["var a = 1;", "print a +", "1;"].into(), ..Default::default() };
+        run_repl(&mut script, &config).unwrap();
+        assert_eq!(script.loaded_from, Some(config.history_path));
+        assert_eq!(script.prompts, ["lox> ", "lox> ", "... ", "lox> "]);
+        assert_eq!(script.history, ["var a = 1;", "print a +", "1;"]);
+        assert_eq!(ReplConfig::default().prompt, "Lox > ");
+        assert_eq!(ReplConfig::default().history_path, PathBuf::from("history.txt"));
+    }
+
+    #[test]
+    fn parses_version_flag() {
+        let args = |args: &[&str]| parse_args(&args.iter().map(|a| a.to_string()).collect::<Vec<_>>());
+        assert_eq!(args(&["loxr", "--version"]), Command::Version);
+        assert_eq!(args(&["loxr", "test.lox"]), Command::File("test.lox".into()));
+        assert_eq!(args(&["loxr"]), Command::Repl);
+        assert_eq!(args(&["loxr", "a.lox", "b.lox"]), Command::Usage);
+    }
+}